@@ -0,0 +1,308 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    player::PlayerCount,
+    timestep::{TickInput, FIXED_UPDATE},
+};
+
+/// Saved next to the executable's working directory, the defaults are used when missing.
+pub const BINDINGS_PATH: &str = "bindings.ron";
+
+/// Systems overriding [`TickActions`] should run after this label.
+pub const READ_ACTIONS_LABEL: &str = "read_actions";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Fire,
+    Confirm,
+    Back,
+    Pause,
+    /// Equips the first weapon of the inventory.
+    #[cfg(feature = "debug")]
+    EquipWeapon,
+    /// Makes an enemy chase the player.
+    #[cfg(feature = "debug")]
+    TestChase,
+}
+
+impl Action {
+    /// Bound separately for each player.
+    pub const PLAYER: [Action; 5] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Fire,
+    ];
+    /// Shared by every player and the menus.
+    pub const COMMON: &[Action] = &[
+        Action::Confirm,
+        Action::Back,
+        Action::Pause,
+        #[cfg(feature = "debug")]
+        Action::EquipWeapon,
+        #[cfg(feature = "debug")]
+        Action::TestChase,
+    ];
+}
+
+/// Keys of each action, an action is triggered by any of its keys.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ActionMap(pub BTreeMap<Action, Vec<KeyCode>>);
+
+impl ActionMap {
+    fn new(bindings: &[(Action, &[KeyCode])]) -> Self {
+        Self(
+            bindings
+                .iter()
+                .map(|(action, keys)| (*action, keys.to_vec()))
+                .collect(),
+        )
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn pressed(&self, action: Action, input: &Input<KeyCode>) -> bool {
+        self.keys(action).iter().any(|&key| input.pressed(key))
+    }
+
+    pub fn just_pressed(&self, action: Action, input: &Input<KeyCode>) -> bool {
+        self.keys(action).iter().any(|&key| input.just_pressed(key))
+    }
+
+    /// Keeps the keys of the action from being seen again on this frame,
+    /// by systems of the state it switches to.
+    pub fn reset(&self, action: Action, input: &mut Input<KeyCode>) {
+        for &key in self.keys(action) {
+            input.reset(key);
+        }
+    }
+}
+
+/// Actions held or just pressed on a tick.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct ActionState {
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub pressed: BTreeSet<Action>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub just_pressed: BTreeSet<Action>,
+}
+
+impl ActionState {
+    pub fn read(map: &ActionMap, input: &Input<KeyCode>) -> Self {
+        let actions = || map.0.keys().copied();
+        Self {
+            pressed: actions()
+                .filter(|&action| map.pressed(action, input))
+                .collect(),
+            just_pressed: actions()
+                .filter(|&action| map.just_pressed(action, input))
+                .collect(),
+        }
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+}
+
+/// Actions of the current tick, [`FIXED_UPDATE`] systems should read them instead of the keys.
+/// Replays record them, so they don't depend on the bindings.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+pub struct TickActions {
+    /// By player index.
+    pub players: Vec<ActionState>,
+    pub common: ActionState,
+}
+
+impl TickActions {
+    /// Actions of a player, players without bindings of their own have none.
+    pub fn player(&self, index: usize) -> Option<&ActionState> {
+        self.players.get(index)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Bindings {
+    /// Used when playing alone.
+    pub solo: ActionMap,
+    /// Used in co-op, by player index.
+    pub players: Vec<ActionMap>,
+    pub common: ActionMap,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use KeyCode::*;
+        Self {
+            solo: ActionMap::new(&[
+                (Action::MoveUp, &[W, Up]),
+                (Action::MoveDown, &[S, Down]),
+                (Action::MoveLeft, &[A, Left]),
+                (Action::MoveRight, &[D, Right]),
+                (Action::Fire, &[Space]),
+            ]),
+            players: vec![
+                ActionMap::new(&[
+                    (Action::MoveUp, &[W]),
+                    (Action::MoveDown, &[S]),
+                    (Action::MoveLeft, &[A]),
+                    (Action::MoveRight, &[D]),
+                    (Action::Fire, &[Space]),
+                ]),
+                ActionMap::new(&[
+                    (Action::MoveUp, &[Up]),
+                    (Action::MoveDown, &[Down]),
+                    (Action::MoveLeft, &[Left]),
+                    (Action::MoveRight, &[Right]),
+                    (Action::Fire, &[RControl]),
+                ]),
+            ],
+            common: ActionMap::new(&[
+                (Action::Confirm, &[Return]),
+                (Action::Back, &[Escape]),
+                (Action::Pause, &[P]),
+                #[cfg(feature = "debug")]
+                (Action::EquipWeapon, &[E]),
+                #[cfg(feature = "debug")]
+                (Action::TestChase, &[C]),
+            ]),
+        }
+    }
+}
+
+impl Bindings {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let bytes = fs::read(path)?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let bindings = ron::ser::to_string_pretty(self, Default::default())?;
+        fs::write(path, bindings)?;
+        Ok(())
+    }
+
+    /// Actions of a player, players without bindings of their own have none.
+    pub fn player(&self, index: usize, player_count: usize) -> Option<&ActionMap> {
+        if player_count > 1 {
+            self.players.get(index)
+        } else {
+            Some(&self.solo)
+        }
+    }
+}
+
+pub struct ActionsPlugin;
+
+impl Plugin for ActionsPlugin {
+    fn build(&self, app: &mut App) {
+        let path = Path::new(BINDINGS_PATH);
+        let bindings = if path.exists() {
+            Bindings::load(path).unwrap_or_else(|err| {
+                warn!("Unable to load bindings, using the defaults: {}", err);
+                Bindings::default()
+            })
+        } else {
+            Bindings::default()
+        };
+        app.insert_resource(bindings)
+            .init_resource::<TickActions>()
+            .add_system_to_stage(FIXED_UPDATE, read_actions.label(READ_ACTIONS_LABEL));
+    }
+}
+
+fn read_actions(
+    input: Res<TickInput>,
+    bindings: Res<Bindings>,
+    player_count: Res<PlayerCount>,
+    mut actions: ResMut<TickActions>,
+) {
+    *actions = TickActions {
+        players: (0..player_count.0)
+            .map(|index| {
+                bindings
+                    .player(index, player_count.0)
+                    .map_or_else(ActionState::default, |map| ActionState::read(map, &input.0))
+            })
+            .collect(),
+        common: ActionState::read(&bindings.common, &input.0),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn action_state_reads_any_bound_key() {
+        let bindings = Bindings::default();
+        let mut input = Input::default();
+        input.press(KeyCode::Up);
+        input.clear();
+        input.press(KeyCode::Space);
+
+        let actions = ActionState::read(&bindings.solo, &input);
+        assert!(actions.pressed(Action::MoveUp));
+        assert!(!actions.just_pressed(Action::MoveUp));
+        assert!(actions.pressed(Action::Fire));
+        assert!(actions.just_pressed(Action::Fire));
+        assert!(!actions.pressed(Action::MoveDown));
+    }
+
+    #[test]
+    fn tick_actions_round_trip_through_ron() {
+        let bindings = Bindings::default();
+        let mut input = Input::default();
+        input.press(KeyCode::W);
+        input.press(KeyCode::RControl);
+        input.press(KeyCode::P);
+        let actions = TickActions {
+            players: bindings
+                .players
+                .iter()
+                .map(|map| ActionState::read(map, &input))
+                .collect(),
+            common: ActionState::read(&bindings.common, &input),
+        };
+
+        let ron = ron::ser::to_string(&actions).unwrap();
+        assert_eq!(ron::de::from_str::<TickActions>(&ron).unwrap(), actions);
+        assert!(actions.player(0).unwrap().pressed(Action::MoveUp));
+        assert!(actions.player(1).unwrap().just_pressed(Action::Fire));
+        assert!(actions.common.just_pressed(Action::Pause));
+    }
+
+    #[test]
+    fn bindings_round_trip_through_file() {
+        let path = env::temp_dir().join("space_battle_test.bindings.ron");
+        let mut bindings = Bindings::default();
+        bindings.solo.0.insert(Action::Fire, vec![KeyCode::J]);
+        bindings.save(&path).unwrap();
+
+        let loaded = Bindings::load(&path).unwrap();
+        fs::remove_file(&path).ok();
+        assert_eq!(loaded.solo.keys(Action::Fire), [KeyCode::J]);
+        assert_eq!(loaded.common.keys(Action::Pause), [KeyCode::P]);
+        assert_eq!(loaded.players.len(), bindings.players.len());
+    }
+}
@@ -2,12 +2,17 @@ mod cooldown;
 mod unit_prefab;
 mod weapon;
 
+use std::collections::HashMap;
+
 use bevy::prelude::*;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "debug")]
 use bevy_inspector_egui::Inspectable;
 
+use crate::pickup::Pickup;
+
 pub use self::{cooldown::*, unit_prefab::*, weapon::*};
 
 #[cfg_attr(feature = "debug", derive(Inspectable))]
@@ -16,9 +21,40 @@ pub struct Scores {
     pub amount: u32,
 }
 #[cfg_attr(feature = "debug", derive(Inspectable))]
-#[derive(Debug, Default, Component, Clone, Serialize, Deserialize)]
+#[derive(Default, Component, Clone, Serialize, Deserialize)]
 pub struct Loot {
     pub score: u32,
+    /// Chance between 0 and 1 of dropping one of `drops`.
+    #[serde(default)]
+    pub drop_chance: f32,
+    #[serde(default)]
+    #[cfg_attr(feature = "debug", inspectable(ignore))]
+    pub drops: Vec<LootDrop>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LootDrop {
+    /// Relative to the other drops of the table.
+    pub weight: u32,
+    pub pickup: Pickup,
+}
+
+impl Loot {
+    pub fn roll(&self, rng: &mut impl Rng) -> Option<&Pickup> {
+        let total = self.drops.iter().map(|drop| drop.weight).sum::<u32>();
+        if total == 0 || !rng.gen_bool(self.drop_chance.clamp(0.0, 1.0) as f64) {
+            return None;
+        }
+        let mut roll = rng.gen_range(0..total);
+        self.drops.iter().find_map(|drop| {
+            if roll < drop.weight {
+                Some(&drop.pickup)
+            } else {
+                roll -= drop.weight;
+                None
+            }
+        })
+    }
 }
 
 #[cfg_attr(feature = "debug", derive(Inspectable))]
@@ -26,6 +62,7 @@ pub struct Loot {
 pub struct Health {
     pub current: u32,
     pub max: u32,
+    pub last_hit_by: Option<Entity>,
 }
 
 impl Health {
@@ -33,10 +70,226 @@ impl Health {
         Self {
             current: amount,
             max: amount,
+            last_hit_by: None,
         }
     }
 
+    pub fn damage(&mut self, amount: u32, source: Option<Entity>) {
+        self.current = self.current.saturating_sub(amount);
+        self.last_hit_by = source;
+    }
+
     pub fn is_dead(&self) -> bool {
         self.current == 0
     }
 }
+
+/// Lives left, including the current one.
+/// Units losing a life with lives left are not despawned, their plugin brings them back.
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component)]
+pub struct Lives {
+    pub remaining: u32,
+    /// Seconds before coming back.
+    pub respawn_delay: f32,
+    /// Seconds of invulnerability after coming back.
+    pub invulnerability: f32,
+}
+
+/// Bullets and explosions don't affect the unit until the timer runs out.
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component)]
+pub struct Invulnerable {
+    #[cfg_attr(feature = "debug", inspectable(ignore))]
+    pub timer: Timer,
+}
+
+/// Absorbs damage before `Health` and regenerates once it hasn't been hit for a while.
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component)]
+pub struct Shield {
+    pub current: u32,
+    pub max: u32,
+    /// Points per second.
+    pub regen_rate: f32,
+    pub regen_progress: f32,
+    #[cfg_attr(feature = "debug", inspectable(ignore))]
+    pub regen_delay: Timer,
+}
+
+impl Shield {
+    /// Returns the damage left after the shield is depleted.
+    pub fn absorb(&mut self, amount: u32) -> u32 {
+        self.regen_delay.reset();
+        self.regen_progress = 0.0;
+        let absorbed = amount.min(self.current);
+        self.current -= absorbed;
+        amount - absorbed
+    }
+}
+
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Debug, Component, Clone, Serialize, Deserialize)]
+pub enum Armor {
+    Flat(u32),
+    /// Percent of damage blocked, from 0 to 100.
+    Percent(f32),
+}
+
+impl Default for Armor {
+    fn default() -> Self {
+        Self::Flat(0)
+    }
+}
+
+impl Armor {
+    pub fn reduce(&self, amount: u32) -> u32 {
+        match *self {
+            Armor::Flat(armor) => amount.saturating_sub(armor),
+            Armor::Percent(percent) => {
+                (amount as f32 * (1.0 - percent.clamp(0.0, 100.0) / 100.0)).round() as u32
+            }
+        }
+    }
+}
+
+/// Damage dealt to units touching this one, again every cooldown while they overlap.
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Debug, Component, Clone, Serialize, Deserialize)]
+pub struct ContactDamage {
+    pub damage: u32,
+    #[serde(default)]
+    pub damage_type: DamageType,
+}
+
+/// Damage multipliers per damage type, types not listed deal full damage.
+#[derive(Debug, Default, Component, Clone, Serialize, Deserialize)]
+pub struct Resistances(pub HashMap<DamageType, f32>);
+
+impl Resistances {
+    pub fn apply(&self, amount: u32, damage_type: DamageType) -> u32 {
+        let multiplier = self.0.get(&damage_type).copied().unwrap_or(1.0).max(0.0);
+        (amount as f32 * multiplier).round() as u32
+    }
+}
+
+/// Damage reaching health: resistances apply first, then shields absorb it before armor reduces it.
+pub fn mitigate_damage(
+    amount: u32,
+    damage_type: DamageType,
+    shield: Option<&mut Shield>,
+    armor: Option<&Armor>,
+    resistances: Option<&Resistances>,
+) -> u32 {
+    let mut damage =
+        resistances.map_or(amount, |resistances| resistances.apply(amount, damage_type));
+    if let Some(shield) = shield {
+        damage = shield.absorb(damage);
+    }
+    if let Some(armor) = armor {
+        damage = armor.reduce(damage);
+    }
+    damage
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn loot(drop_chance: f32, weights: &[u32]) -> Loot {
+        Loot {
+            score: 0,
+            drop_chance,
+            drops: weights
+                .iter()
+                .enumerate()
+                .map(|(i, &weight)| LootDrop {
+                    weight,
+                    pickup: Pickup::Repair(i as u32),
+                })
+                .collect(),
+        }
+    }
+
+    fn shield(current: u32) -> Shield {
+        Shield {
+            current,
+            max: current,
+            regen_rate: 0.0,
+            regen_progress: 0.0,
+            regen_delay: Timer::from_seconds(1.0, false),
+        }
+    }
+
+    #[test]
+    fn loot_drops_follow_weights() {
+        let loot = loot(1.0, &[1, 3, 0]);
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut counts = [0; 3];
+        for _ in 0..4000 {
+            match loot.roll(&mut rng) {
+                Some(Pickup::Repair(i)) => counts[*i as usize] += 1,
+                _ => panic!("Every roll should drop one of the drops"),
+            }
+        }
+        assert_eq!(counts[2], 0);
+        let ratio = counts[1] as f32 / counts[0] as f32;
+        assert!((2.5..3.5).contains(&ratio), "Drop ratio was {}", ratio);
+    }
+
+    #[test]
+    fn loot_without_chance_or_weights_drops_nothing() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            assert!(loot(0.0, &[1, 1]).roll(&mut rng).is_none());
+            assert!(loot(1.0, &[0, 0]).roll(&mut rng).is_none());
+            assert!(loot(1.0, &[]).roll(&mut rng).is_none());
+        }
+    }
+
+    #[test]
+    fn shield_absorbs_damage_before_armor() {
+        let mut shield = shield(4);
+        let armor = Armor::Percent(50.0);
+        // Armor first would leave 10 / 2 - 4 = 1.
+        assert_eq!(
+            mitigate_damage(
+                10,
+                DamageType::Kinetic,
+                Some(&mut shield),
+                Some(&armor),
+                None
+            ),
+            3
+        );
+        assert_eq!(shield.current, 0);
+    }
+
+    #[test]
+    fn resistances_apply_before_shield_and_armor() {
+        let mut shield = shield(4);
+        let armor = Armor::Flat(1);
+        let resistances = Resistances([(DamageType::Energy, 0.5)].into_iter().collect());
+        assert_eq!(
+            mitigate_damage(
+                20,
+                DamageType::Energy,
+                Some(&mut shield),
+                Some(&armor),
+                Some(&resistances),
+            ),
+            5
+        );
+        assert_eq!(resistances.apply(20, DamageType::Kinetic), 20);
+    }
+
+    #[test]
+    fn shield_only_absorbs_what_it_has() {
+        let mut shield = shield(3);
+        assert_eq!(shield.absorb(2), 0);
+        assert_eq!(shield.absorb(5), 4);
+        assert_eq!(shield.current, 0);
+    }
+}
@@ -1,13 +1,18 @@
 use bevy::{prelude::*, reflect::TypeUuid};
+#[cfg(feature = "debug")]
+use bevy_inspector_egui::Inspectable;
 use heron::CollisionShape;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    prefab::{self, Prefab},
+    prefab::{self, Prefab, PrefabHandle},
     prefab_loader,
 };
 
-use super::{Health, Loot, WeaponSlotPrefab};
+use super::{
+    Armor, ContactDamage, Health, Lives, Loot, Resistances, Shield, WeaponInventory, WeaponPrefab,
+    WeaponSlotPrefab,
+};
 
 pub struct UnitLoader;
 prefab_loader!(UnitLoader, UnitPrefab, ["unit.ron"]);
@@ -17,16 +22,129 @@ prefab_loader!(UnitLoader, UnitPrefab, ["unit.ron"]);
 #[serde(rename = "Unit")]
 pub struct UnitPrefab {
     pub name: String,
+    /// Offered on the ship select screen.
+    #[serde(default)]
+    pub playable: bool,
     pub health: u32,
     pub weapon_slots: Vec<WeaponSlotPrefab>,
     pub loot: Loot,
     pub body: String,
+    #[serde(default)]
+    pub shield: Option<ShieldPrefab>,
+    #[serde(default)]
+    pub armor: Option<Armor>,
+    #[serde(default)]
+    pub resistances: Resistances,
+    /// Weapons carried without being equipped.
+    #[serde(default)]
+    pub inventory: Vec<PrefabHandle<WeaponPrefab>>,
+    #[serde(default)]
+    pub lives: Option<LivesPrefab>,
+    /// Dealt to ships rammed by the unit.
+    #[serde(default)]
+    pub contact_damage: Option<ContactDamage>,
+    #[serde(default)]
+    pub engine: Engine,
+    #[serde(default)]
+    pub collider: Collider,
+    /// Multiplies the size of the `body` image.
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Engine {
+    /// Units per second.
+    pub speed: f32,
+    /// Units per second squared, only used by units moving with a `Velocity`.
+    pub acceleration: f32,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self {
+            speed: 100.0,
+            acceleration: 1000.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum Collider {
+    Box { size: Vec2 },
+    Circle { radius: f32 },
+    Capsule { height: f32, radius: f32 },
+}
+
+impl Default for Collider {
+    fn default() -> Self {
+        Self::Box {
+            size: Vec2::splat(32.0),
+        }
+    }
+}
+
+impl Collider {
+    pub fn shape(&self) -> CollisionShape {
+        match *self {
+            Collider::Box { size } => CollisionShape::Cuboid {
+                half_extends: size.extend(0.0) / 2.0,
+                border_radius: None,
+            },
+            Collider::Circle { radius } => CollisionShape::Sphere { radius },
+            Collider::Capsule { height, radius } => CollisionShape::Capsule {
+                half_segment: height / 2.0,
+                radius,
+            },
+        }
+    }
+}
+
+/// Size of the sprite relative to its image, applied once the image is loaded.
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component)]
+pub struct SpriteScale(pub f32);
+
+/// Asset the unit was spawned from, its stats are updated when the asset is reloaded.
+#[derive(Component)]
+pub struct SourceUnit(pub Handle<UnitPrefab>);
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename = "Shield")]
+pub struct ShieldPrefab {
+    pub amount: u32,
+    /// Seconds without hits before the shield starts to regenerate.
+    pub delay: f32,
+    /// Points per second.
+    pub regen: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename = "Lives")]
+pub struct LivesPrefab {
+    pub count: u32,
+    pub respawn_delay: f32,
+    pub invulnerability: f32,
+}
+
+impl UnitPrefab {
+    /// Components tuned in the asset without affecting the unit's current state.
+    pub fn stats(&self) -> (Engine, CollisionShape, SpriteScale) {
+        (
+            self.engine.clone(),
+            self.collider.shape(),
+            SpriteScale(self.scale),
+        )
+    }
 }
 
 impl Prefab for UnitPrefab {
     fn apply(&self, entity: Entity, world: &mut World) {
-        let size = Vec2::splat(32.0);
-
         let texture: Handle<Image> = world.resource::<AssetServer>().load(&self.body);
 
         world
@@ -35,13 +153,41 @@ impl Prefab for UnitPrefab {
                 texture,
                 ..default()
             })
-            .insert(CollisionShape::Cuboid {
-                half_extends: size.extend(0.0) / 2.0,
-                border_radius: None,
-            })
+            .insert_bundle(self.stats())
             .insert(Health::new(self.health))
             .insert(Name::new(self.name.clone()))
-            .insert(self.loot.clone());
+            .insert(self.loot.clone())
+            .insert(self.resistances.clone());
+
+        if let Some(shield) = &self.shield {
+            world.entity_mut(entity).insert(Shield {
+                current: shield.amount,
+                max: shield.amount,
+                regen_rate: shield.regen,
+                regen_progress: 0.0,
+                regen_delay: Timer::from_seconds(shield.delay, false),
+            });
+        }
+        if let Some(armor) = &self.armor {
+            world.entity_mut(entity).insert(armor.clone());
+        }
+        if let Some(contact_damage) = &self.contact_damage {
+            world.entity_mut(entity).insert(contact_damage.clone());
+        }
+        if let Some(lives) = &self.lives {
+            world.entity_mut(entity).insert(Lives {
+                remaining: lives.count,
+                respawn_delay: lives.respawn_delay,
+                invulnerability: lives.invulnerability,
+            });
+        }
+
+        let weapons = self
+            .inventory
+            .iter()
+            .map(|weapon| weapon.as_handle(world))
+            .collect();
+        world.entity_mut(entity).insert(WeaponInventory { weapons });
 
         self.weapon_slots.apply(entity, world);
     }
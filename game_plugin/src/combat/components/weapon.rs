@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     prefab::{self, Prefab, PrefabHandle},
-    prefab_loader, Lifetime,
+    prefab_loader, Lifetime, PhysicsLayers,
 };
 
 pub struct WeaponLoader;
@@ -22,36 +22,346 @@ use super::Cooldown;
 #[derive(Debug, Default, Component, Clone, Serialize, Deserialize)]
 pub struct Damage(pub u32);
 
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Debug, Component, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DamageType {
+    Kinetic,
+    Energy,
+    Explosive,
+}
+
+impl Default for DamageType {
+    fn default() -> Self {
+        Self::Kinetic
+    }
+}
+
 #[cfg_attr(feature = "debug", derive(Inspectable))]
 #[derive(Component, Debug, Clone)]
 pub struct Weapon {
-    pub bullet: Handle<BulletPrefab>,
+    /// Not set for beam weapons.
+    pub bullet: Option<Handle<BulletPrefab>>,
 }
 
 #[cfg_attr(feature = "debug", derive(Inspectable))]
 #[derive(Default, Clone, Component)]
 pub struct WeaponSlot;
 
+/// Prefab of the weapon equipped in a slot, used to move it to another slot or the inventory.
+/// Picked up weapons with the same name upgrade it instead of being equipped next to it.
+#[derive(Component, Clone)]
+pub struct EquippedWeapon(pub WeaponPrefab);
+
+/// Weapons carried by a unit without being equipped.
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component, Default)]
+pub struct WeaponInventory {
+    pub weapons: Vec<Handle<WeaponPrefab>>,
+}
+
 #[cfg_attr(feature = "debug", derive(Inspectable))]
 #[derive(Component)]
 pub struct Bullet {
     pub damage: u32,
+    pub damage_type: DamageType,
+}
+
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component)]
+pub struct Projectile {
+    pub acceleration: f32,
+    pub max_range: Option<f32>,
+    pub traveled: f32,
+}
+
+impl Projectile {
+    pub fn out_of_range(&self) -> bool {
+        self.max_range
+            .map_or(false, |max_range| self.traveled >= max_range)
+    }
+}
+
+/// Lets a bullet go through `remaining` more units, hitting each of them only once.
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component)]
+pub struct Pierce {
+    pub remaining: u32,
+    #[cfg_attr(feature = "debug", inspectable(ignore))]
+    pub hit: Vec<Entity>,
+}
+
+/// Reflects a bullet off walls `remaining` more times.
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component)]
+pub struct Bounce {
+    pub remaining: u32,
+}
+
+/// Steers a bullet toward the nearest unit its collision layers interact with.
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Homing {
+    /// Degrees per second.
+    pub turn_rate: f32,
+    pub radius: f32,
+    /// Narrows targets down to a single layer.
+    #[serde(default)]
+    #[cfg_attr(feature = "debug", inspectable(ignore))]
+    pub layer: Option<PhysicsLayers>,
+    #[serde(skip)]
+    pub target: Option<Entity>,
+}
+
+/// Damages every unit the bullet's collision layers interact with in `radius`,
+/// on impact or when the bullet's lifetime runs out.
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component, Clone, Serialize, Deserialize)]
+pub struct Explosion {
+    pub radius: f32,
+    /// Fraction of the damage lost at the edge of the radius, scaled linearly with distance.
+    #[serde(default)]
+    pub falloff: f32,
+    /// Image of the explosion visual, a tinted square is used when not set.
+    #[serde(default)]
+    pub effect: Option<String>,
+}
+
+impl Explosion {
+    pub fn damage_at(&self, damage: u32, distance: f32) -> u32 {
+        let ratio = (distance / self.radius).clamp(0.0, 1.0);
+        (damage as f32 * (1.0 - self.falloff.clamp(0.0, 1.0) * ratio)).round() as u32
+    }
+}
+
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FirePattern {
+    /// Bullets fired at once, evenly spread over `spread` degrees.
+    pub shots: u32,
+    pub spread: f32,
+    /// Volleys fired per shot, `burst_interval` seconds apart or all at once when it is 0.
+    pub burst: u32,
+    pub burst_interval: f32,
+    /// Maximum random deviation of every bullet in degrees.
+    pub inaccuracy: f32,
+    /// Takes turns with the other alternating weapons of the unit,
+    /// the cooldown is then the time between two shots of any of them.
+    pub alternate: bool,
+}
+
+impl Default for FirePattern {
+    fn default() -> Self {
+        Self {
+            shots: 1,
+            spread: 0.0,
+            burst: 1,
+            burst_interval: 0.1,
+            inaccuracy: 0.0,
+            alternate: false,
+        }
+    }
+}
+
+impl FirePattern {
+    /// Angles of every bullet in a volley relative to the weapon, in degrees.
+    pub fn angles(&self) -> impl Iterator<Item = f32> + '_ {
+        (0..self.shots).map(|i| {
+            if self.shots > 1 {
+                self.spread * (i as f32 / (self.shots - 1) as f32 - 0.5)
+            } else {
+                0.0
+            }
+        })
+    }
+}
+
+/// Builds up while firing and cools down continuously.
+/// An overheated weapon can't fire until it has fully cooled down.
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component)]
+pub struct Heat {
+    pub current: f32,
+    pub max: f32,
+    /// Heat gained by every shot of a bullet weapon.
+    pub per_shot: f32,
+    /// Heat lost per second.
+    pub cooling_rate: f32,
+    pub overheated: bool,
+}
+
+impl Heat {
+    pub fn add(&mut self, amount: f32) {
+        self.current = (self.current + amount).min(self.max);
+        if self.current >= self.max {
+            self.overheated = true;
+        }
+    }
+
+    pub fn cool(&mut self, amount: f32) {
+        self.current = (self.current - amount).max(0.0);
+        if self.current == 0.0 {
+            self.overheated = false;
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename = "Heat")]
+pub struct HeatPrefab {
+    pub max: f32,
+    #[serde(default)]
+    pub per_shot: f32,
+    pub cooling_rate: f32,
+}
+
+/// Rounds left before the weapon has to reload, every shot uses one round.
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component)]
+pub struct Magazine {
+    pub rounds: u32,
+    pub capacity: u32,
+    #[cfg_attr(feature = "debug", inspectable(ignore))]
+    pub reload: Timer,
+}
+
+impl Magazine {
+    pub fn is_reloading(&self) -> bool {
+        self.rounds == 0
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename = "Magazine")]
+pub struct MagazinePrefab {
+    /// A magazine without rounds would reload forever, the weapon has no magazine instead.
+    pub capacity: u32,
+    /// Seconds.
+    pub reload_time: f32,
+}
+
+/// Continuous ray damaging the first unit in front of the weapon while it fires.
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component)]
+pub struct Beam {
+    pub length: f32,
+    pub width: f32,
+    pub damage_per_second: f32,
+    pub heat_per_second: f32,
+    /// Set on the ticks the beam is fired.
+    pub shooter: Option<Entity>,
+    pub damage_progress: f32,
+    pub sprite: Option<Entity>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename = "Beam")]
+pub struct BeamPrefab {
+    pub length: f32,
+    pub width: f32,
+    pub damage_per_second: f32,
+    #[serde(default)]
+    pub heat_per_second: f32,
+}
+
+/// Turn of the unit's alternating weapons, counted in the order of its weapon slots.
+#[derive(Component, Default)]
+pub struct NextBarrel(pub usize);
+
+/// Remaining volleys of a burst in progress.
+#[derive(Component)]
+pub struct Burst {
+    pub shooter: Entity,
+    pub remaining: u32,
+    pub timer: Timer,
 }
 
 #[derive(Serialize, Deserialize, TypeUuid, Clone)]
 #[uuid = "4825c543-fe54-4aec-82b8-5cbf413f3a88"]
 #[serde(rename = "Weapon")]
 pub struct WeaponPrefab {
-    pub bullet: PrefabHandle<BulletPrefab>,
+    /// Shown in the shop, and used to recognize a picked up weapon as one already equipped.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Weapons without a price are not sold in the shop.
+    #[serde(default)]
+    pub price: Option<u32>,
+    #[serde(default)]
+    pub bullet: Option<PrefabHandle<BulletPrefab>>,
+    /// Fires a continuous beam instead of bullets, `damage` and `cooldown` are not used.
+    #[serde(default)]
+    pub beam: Option<BeamPrefab>,
+    #[serde(default)]
     pub damage: Damage,
+    #[serde(default)]
+    pub damage_type: DamageType,
+    #[serde(default)]
     pub cooldown: Cooldown,
+    #[serde(default)]
+    pub heat: Option<HeatPrefab>,
+    #[serde(default)]
+    pub magazine: Option<MagazinePrefab>,
+    #[serde(default)]
+    pub pattern: FirePattern,
+    /// Levels the weapon can be upgraded to, in order.
+    #[serde(default)]
+    pub upgrades: Vec<WeaponUpgrade>,
+    /// Level the weapon is equipped at, kept when it goes back to an inventory.
+    #[serde(default)]
+    pub level: usize,
 }
 
-impl Prefab for WeaponPrefab {
-    fn apply(&self, entity: Entity, world: &mut World) {
-        let bullet_handle = self.bullet.as_handle(world);
+/// Fields replacing the ones of the previous level, unset fields are kept.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename = "Upgrade")]
+pub struct WeaponUpgrade {
+    #[serde(default)]
+    pub damage: Option<Damage>,
+    #[serde(default)]
+    pub cooldown: Option<Cooldown>,
+    #[serde(default)]
+    pub bullet: Option<PrefabHandle<BulletPrefab>>,
+    #[serde(default)]
+    pub pattern: Option<FirePattern>,
+}
+
+/// Upgrade level of an equipped weapon, 0 being the base weapon.
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component)]
+pub struct WeaponLevel {
+    pub current: usize,
+    pub max: usize,
+}
+
+impl WeaponPrefab {
+    /// The weapon with the first `level` upgrades applied.
+    pub fn at_level(&self, level: usize) -> Self {
+        let mut weapon = self.clone();
+        for upgrade in self.upgrades.iter().take(level) {
+            if let Some(damage) = &upgrade.damage {
+                weapon.damage = damage.clone();
+            }
+            if let Some(cooldown) = &upgrade.cooldown {
+                weapon.cooldown = cooldown.clone();
+            }
+            if let Some(bullet) = &upgrade.bullet {
+                weapon.bullet = Some(bullet.clone());
+            }
+            if let Some(pattern) = &upgrade.pattern {
+                weapon.pattern = pattern.clone();
+            }
+        }
+        weapon
+    }
+
+    fn apply_level(&self, level: usize, entity: Entity, world: &mut World) {
+        let level = level.min(self.upgrades.len());
+        let weapon = self.at_level(level);
+        let bullet_handle = weapon.bullet.as_ref().map(|bullet| bullet.as_handle(world));
 
         let mut entity = world.entity_mut(entity);
+        // Keeps the sprite of a beam when the weapon is applied again in place.
+        let beam_sprite = entity.get::<Beam>().and_then(|beam| beam.sprite);
         entity
             // .insert_bundle(prefab::SpriteBundle {
             //     sprite: Sprite {
@@ -61,11 +371,75 @@ impl Prefab for WeaponPrefab {
             //     },
             //     ..default()
             // })
-            .insert(self.damage.clone())
+            .insert(weapon.damage.clone())
+            .insert(weapon.damage_type)
             .insert(Weapon {
                 bullet: bullet_handle,
             })
-            .insert(self.cooldown.clone());
+            .insert(weapon.pattern.clone());
+
+        match &weapon.beam {
+            Some(beam) => {
+                entity.insert(Beam {
+                    length: beam.length,
+                    width: beam.width,
+                    damage_per_second: beam.damage_per_second,
+                    heat_per_second: beam.heat_per_second,
+                    shooter: None,
+                    damage_progress: 0.0,
+                    sprite: beam_sprite,
+                });
+            }
+            None => {
+                entity.insert(weapon.cooldown.clone());
+            }
+        }
+        // Upgrading a weapon in place keeps its heat and rounds,
+        // slots are emptied before another weapon is equipped.
+        if let Some(heat) = weapon.heat.as_ref().filter(|_| !entity.contains::<Heat>()) {
+            entity.insert(Heat {
+                current: 0.0,
+                max: heat.max,
+                per_shot: heat.per_shot,
+                cooling_rate: heat.cooling_rate,
+                overheated: false,
+            });
+        }
+        if let Some(magazine) = weapon
+            .magazine
+            .as_ref()
+            .filter(|magazine| magazine.capacity > 0 && !entity.contains::<Magazine>())
+        {
+            entity.insert(Magazine {
+                rounds: magazine.capacity,
+                capacity: magazine.capacity,
+                reload: Timer::from_seconds(magazine.reload_time, false),
+            });
+        }
+        entity
+            .insert(EquippedWeapon(self.clone()))
+            .insert(WeaponLevel {
+                current: level,
+                max: self.upgrades.len(),
+            });
+    }
+}
+
+impl Prefab for WeaponPrefab {
+    fn apply(&self, entity: Entity, world: &mut World) {
+        self.apply_level(self.level, entity, world);
+    }
+}
+
+/// Applies a weapon at one of its upgrade levels.
+pub struct WeaponAtLevel {
+    pub weapon: WeaponPrefab,
+    pub level: usize,
+}
+
+impl Prefab for WeaponAtLevel {
+    fn apply(&self, entity: Entity, world: &mut World) {
+        self.weapon.apply_level(self.level, entity, world);
     }
 }
 
@@ -101,6 +475,33 @@ impl Prefab for WeaponSlotPrefab {
 pub struct BulletPrefab {
     pub size: Vec2,
     pub body: String,
+    #[serde(default = "default_bullet_speed")]
+    pub speed: f32,
+    /// Seconds.
+    #[serde(default = "default_bullet_lifetime")]
+    pub lifetime: f32,
+    #[serde(default)]
+    pub acceleration: f32,
+    #[serde(default)]
+    pub max_range: Option<f32>,
+    /// Units the bullet goes through before being despawned.
+    #[serde(default)]
+    pub pierce: u32,
+    /// Walls the bullet bounces off instead of passing through them.
+    #[serde(default)]
+    pub bounce: u32,
+    #[serde(default)]
+    pub homing: Option<Homing>,
+    #[serde(default)]
+    pub explosion: Option<Explosion>,
+}
+
+fn default_bullet_speed() -> f32 {
+    300.0
+}
+
+fn default_bullet_lifetime() -> f32 {
+    1.0
 }
 
 impl Prefab for BulletPrefab {
@@ -113,7 +514,12 @@ impl Prefab for BulletPrefab {
                 ..default()
             })
             .insert(Lifetime {
-                timer: Timer::from_seconds(1.0, false),
+                timer: Timer::from_seconds(self.lifetime, false),
+            })
+            .insert(Projectile {
+                acceleration: self.acceleration,
+                max_range: self.max_range,
+                traveled: 0.0,
             })
             .insert(RigidBody::KinematicVelocityBased)
             .insert(SensorShape)
@@ -121,5 +527,23 @@ impl Prefab for BulletPrefab {
                 half_extends: self.size.extend(0.0) / 2.0,
                 border_radius: None,
             });
+
+        if self.pierce > 0 {
+            world.entity_mut(entity).insert(Pierce {
+                remaining: self.pierce,
+                hit: Vec::new(),
+            });
+        }
+        if self.bounce > 0 {
+            world.entity_mut(entity).insert(Bounce {
+                remaining: self.bounce,
+            });
+        }
+        if let Some(homing) = &self.homing {
+            world.entity_mut(entity).insert(homing.clone());
+        }
+        if let Some(explosion) = &self.explosion {
+            world.entity_mut(entity).insert(explosion.clone());
+        }
     }
 }
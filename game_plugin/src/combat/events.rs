@@ -1,9 +1,10 @@
 use bevy::prelude::*;
+use heron::CollisionLayers;
 
-use super::WeaponPrefab;
+use super::{DamageType, Explosion, WeaponPrefab};
 
-// TODO: Which entity to reward
 pub struct RewardEvent {
+    pub receiver: Entity,
     pub score: u32,
 }
 
@@ -18,9 +19,42 @@ pub struct SpawnBulletEvent {
 
 pub enum ContactEvent {
     HealthBullet(Entity, Entity),
+    BulletWall(Entity, Entity),
+    /// Two units touching each other, each deals its `ContactDamage` to the other.
+    Ram(Entity, Entity),
 }
 
+/// Sent when an explosive bullet detonates, also used to spawn the explosion visual.
+pub struct ExplosionEvent {
+    pub position: Vec2,
+    pub explosion: Explosion,
+    pub damage: u32,
+    pub damage_type: DamageType,
+    pub layers: Option<CollisionLayers>,
+    pub owner: Option<Entity>,
+    /// Unit hit directly, it already took the full damage.
+    pub ignore: Option<Entity>,
+}
+
+/// The weapon previously equipped in the slot is put back into the unit's inventory.
 pub struct EquipWeaponEvent {
     pub slot_entity: Entity,
     pub weapon: WeaponPrefab,
 }
+
+/// Moves the weapon equipped in the slot to its next upgrade level, if there is one.
+pub struct UpgradeWeaponEvent {
+    pub slot_entity: Entity,
+}
+
+/// Empties the slot, putting its weapon back into the unit's inventory.
+pub struct UnequipWeaponEvent {
+    pub slot_entity: Entity,
+}
+
+/// Exchanges the weapons of two slots, either of them may be empty.
+/// Swapped weapons start over with a fresh cooldown, heat and magazine.
+pub struct SwapWeaponsEvent {
+    pub first_slot: Entity,
+    pub second_slot: Entity,
+}
@@ -6,11 +6,33 @@ use bevy::prelude::*;
 #[cfg(feature = "debug")]
 use bevy_inspector_egui::RegisterInspectable;
 
-use crate::prefab::RegisterPrefab;
+#[cfg(feature = "debug")]
+use crate::replay::REPLAY_INPUT_LABEL;
+use crate::{
+    pickup::APPLY_PICKUPS_LABEL,
+    prefab::RegisterPrefab,
+    states::GameState,
+    timestep::{AddTickEvent, FIXED_UPDATE},
+    TRACK_LIFETIME_LABEL,
+};
 
 use self::systems::*;
 pub use self::{components::*, events::*};
 
+/// Systems sending [`ShootEvent`] in [`FIXED_UPDATE`] should have this label,
+/// so bullets are spawned on the same tick.
+pub const SHOOT_LABEL: &str = "shoot";
+const HANDLE_SHOOT_LABEL: &str = "handle_shoot";
+const SPAWN_BULLETS_LABEL: &str = "spawn_bullets";
+const UPDATE_BEAMS_LABEL: &str = "update_beams";
+const FIND_CONTACTS_LABEL: &str = "find_contacts";
+const HANDLE_CONTACTS_LABEL: &str = "handle_contacts";
+const DETONATE_LABEL: &str = "detonate";
+const HANDLE_EXPLOSIONS_LABEL: &str = "handle_explosions";
+const MOVE_PROJECTILES_LABEL: &str = "move_projectiles";
+/// Systems reacting to units losing a life should run after it, [`Lives`] are updated by then.
+pub const DESPAWN_DEAD_LABEL: &str = "despawn_dead";
+
 pub struct CombatPlugin;
 
 impl Plugin for CombatPlugin {
@@ -18,28 +40,127 @@ impl Plugin for CombatPlugin {
         #[cfg(feature = "debug")]
         app.register_inspectable::<Weapon>()
             .register_inspectable::<WeaponSlot>()
+            .register_inspectable::<WeaponInventory>()
+            .register_inspectable::<WeaponLevel>()
+            .register_inspectable::<FirePattern>()
+            .register_inspectable::<Heat>()
+            .register_inspectable::<Magazine>()
+            .register_inspectable::<Beam>()
+            .register_inspectable::<DamageType>()
             .register_inspectable::<Bullet>()
+            .register_inspectable::<Projectile>()
+            .register_inspectable::<Pierce>()
+            .register_inspectable::<Bounce>()
+            .register_inspectable::<Homing>()
+            .register_inspectable::<Explosion>()
             .register_inspectable::<Loot>()
-            .register_inspectable::<Health>();
+            .register_inspectable::<Health>()
+            .register_inspectable::<Lives>()
+            .register_inspectable::<Invulnerable>()
+            .register_inspectable::<Shield>()
+            .register_inspectable::<Armor>()
+            .register_inspectable::<ContactDamage>()
+            .register_inspectable::<Engine>()
+            .register_inspectable::<SpriteScale>();
         app.register_prefab::<UnitPrefab>()
             .register_prefab::<WeaponPrefab>()
             .register_prefab::<BulletPrefab>()
             .add_asset_loader(UnitLoader)
             .add_asset_loader(WeaponLoader)
             .add_asset_loader(BulletLoader)
-            .add_event::<EquipWeaponEvent>()
-            .add_event::<ShootEvent>()
-            .add_event::<SpawnBulletEvent>()
-            .add_event::<RewardEvent>()
-            .add_event::<ContactEvent>()
-            .add_system(equip_weapon)
-            .add_system(handle_intersections)
-            .add_system(handle_contacts)
-            .add_system(despawn_dead)
-            .add_system(update_cooldowns)
-            .add_system(handle_shoot_events)
-            .add_system(spawn_bullets)
-            .add_system(apply_score_reward)
-            .add_system(test_equip_weapon);
+            .add_tick_event::<EquipWeaponEvent>()
+            .add_tick_event::<UnequipWeaponEvent>()
+            .add_tick_event::<SwapWeaponsEvent>()
+            .add_tick_event::<UpgradeWeaponEvent>()
+            .add_tick_event::<ShootEvent>()
+            .add_tick_event::<SpawnBulletEvent>()
+            .add_tick_event::<RewardEvent>()
+            .add_tick_event::<ContactEvent>()
+            .add_tick_event::<ExplosionEvent>()
+            .init_resource::<ShipContacts>()
+            .add_system(reload_unit_stats)
+            .add_system(scale_sprites)
+            .add_system_set_to_stage(
+                FIXED_UPDATE,
+                // Items bought in the shop are equipped before gameplay resumes.
+                SystemSet::new()
+                    .with_run_criteria(GameState::in_game)
+                    .with_system(equip_weapon.after(APPLY_PICKUPS_LABEL))
+                    .with_system(unequip_weapon)
+                    .with_system(swap_weapons)
+                    .with_system(upgrade_weapons.after(APPLY_PICKUPS_LABEL)),
+            )
+            .add_system_set_to_stage(
+                FIXED_UPDATE,
+                // Weapons and bullets are frozen while the shop or pause sits on top of gameplay.
+                // Damage is dealt in a fixed order, so are the draws of `GameRng`.
+                SystemSet::on_update(GameState::Gameplay)
+                    .with_system(update_cooldowns)
+                    .with_system(regenerate_shields.before(UPDATE_BEAMS_LABEL))
+                    .with_system(update_invulnerability)
+                    .with_system(
+                        handle_shoot_events
+                            .label(HANDLE_SHOOT_LABEL)
+                            .after(SHOOT_LABEL),
+                    )
+                    .with_system(update_bursts.label(HANDLE_SHOOT_LABEL).after(SHOOT_LABEL))
+                    .with_system(fire_beams.label(HANDLE_SHOOT_LABEL).after(SHOOT_LABEL))
+                    .with_system(
+                        update_beams
+                            .label(UPDATE_BEAMS_LABEL)
+                            .after(HANDLE_SHOOT_LABEL)
+                            .after(APPLY_PICKUPS_LABEL),
+                    )
+                    .with_system(cool_weapons)
+                    .with_system(reload_magazines)
+                    .with_system(
+                        spawn_bullets
+                            .label(SPAWN_BULLETS_LABEL)
+                            .after(HANDLE_SHOOT_LABEL),
+                    )
+                    .with_system(move_projectiles.label(MOVE_PROJECTILES_LABEL))
+                    .with_system(steer_homing_bullets)
+                    .with_system(
+                        detonate_expired_bullets
+                            .label(DETONATE_LABEL)
+                            .after(TRACK_LIFETIME_LABEL)
+                            .after(MOVE_PROJECTILES_LABEL),
+                    )
+                    .with_system(handle_intersections.label(FIND_CONTACTS_LABEL))
+                    .with_system(track_ship_contacts.label(FIND_CONTACTS_LABEL))
+                    .with_system(repeat_ship_contacts.label(FIND_CONTACTS_LABEL))
+                    .with_system(
+                        handle_contacts
+                            .label(HANDLE_CONTACTS_LABEL)
+                            .after(FIND_CONTACTS_LABEL)
+                            .after(UPDATE_BEAMS_LABEL)
+                            .after(MOVE_PROJECTILES_LABEL),
+                    )
+                    .with_system(
+                        handle_explosions
+                            .label(HANDLE_EXPLOSIONS_LABEL)
+                            .after(HANDLE_CONTACTS_LABEL)
+                            .after(DETONATE_LABEL),
+                    )
+                    .with_system(
+                        spawn_explosion_effects
+                            .after(HANDLE_CONTACTS_LABEL)
+                            .after(DETONATE_LABEL),
+                    )
+                    .with_system(
+                        despawn_dead
+                            .label(DESPAWN_DEAD_LABEL)
+                            .after(HANDLE_EXPLOSIONS_LABEL)
+                            .after(SPAWN_BULLETS_LABEL),
+                    )
+                    .with_system(apply_score_reward.after(DESPAWN_DEAD_LABEL)),
+            );
+
+        #[cfg(feature = "debug")]
+        app.add_system_set_to_stage(
+            FIXED_UPDATE,
+            SystemSet::on_update(GameState::Gameplay)
+                .with_system(test_equip_weapon.after(REPLAY_INPUT_LABEL)),
+        );
     }
 }
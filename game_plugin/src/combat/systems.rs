@@ -1,30 +1,68 @@
+#![allow(clippy::type_complexity)]
+
+use std::collections::{HashMap, HashSet};
+
 use bevy::prelude::*;
-use heron::prelude::*;
+use heron::{prelude::*, rapier_plugin::PhysicsWorld};
+use rand::Rng;
 
-use crate::{player::Player, prefab::EntityPrefabCommands, Owner};
+use crate::{
+    pickup::{ScoreMultiplier, SpawnPickupEvent},
+    player::Respawning,
+    prefab::EntityPrefabCommands,
+    rng::GameRng,
+    timestep::Timestep,
+    Lifetime, Owner, TickCollisionEvent, Wall,
+};
 
 use super::{
-    components::{Bullet, Cooldown, Health, Loot, Scores, Weapon},
-    events::{ContactEvent, RewardEvent, ShootEvent, SpawnBulletEvent},
-    BulletPrefab, Damage, EquipWeaponEvent, WeaponPrefab, WeaponSlot,
+    components::{
+        mitigate_damage, Armor, Beam, Bounce, Bullet, Burst, ContactDamage, Cooldown,
+        EquippedWeapon, Explosion, FirePattern, Health, Heat, Homing, Invulnerable, Lives, Loot,
+        Magazine, NextBarrel, Pierce, Projectile, Resistances, Scores, Shield, Weapon,
+        WeaponAtLevel, WeaponInventory, WeaponLevel,
+    },
+    events::{
+        ContactEvent, ExplosionEvent, RewardEvent, ShootEvent, SpawnBulletEvent, SwapWeaponsEvent,
+        UnequipWeaponEvent, UpgradeWeaponEvent,
+    },
+    BulletPrefab, Damage, DamageType, EquipWeaponEvent, SourceUnit, SpriteScale, UnitPrefab,
+    WeaponPrefab, WeaponSlot,
 };
 
+/// Seconds between visibility toggles of invulnerable units.
+const BLINK_INTERVAL: f32 = 0.1;
+/// Seconds between two ramming hits of units staying in contact.
+const RAM_COOLDOWN: f32 = 0.5;
+
+/// Units currently touching each other, with the time until they ram each other again.
+#[derive(Default)]
+pub struct ShipContacts {
+    pairs: HashMap<(Entity, Entity), Timer>,
+}
+
 pub fn handle_intersections(
-    mut collision_events: EventReader<CollisionEvent>,
+    mut collision_events: EventReader<TickCollisionEvent>,
     bullets: Query<&Bullet>,
     healths: Query<&Health>,
+    walls: Query<&Wall>,
     mut contact_events: EventWriter<ContactEvent>,
 ) {
-    for (data1, data2) in collision_events.iter().filter_map(|e| match e {
+    for (data1, data2) in collision_events.iter().filter_map(|e| match &e.0 {
         CollisionEvent::Started(data1, data2) => Some((data1, data2)),
         _ => None,
     }) {
         let entity1 = data1.rigid_body_entity();
         let entity2 = data2.rigid_body_entity();
 
-        let mut check_bullet = |health_entity, bullet_entity| {
-            if healths.get(health_entity).is_ok() && bullets.get(bullet_entity).is_ok() {
-                contact_events.send(ContactEvent::HealthBullet(health_entity, bullet_entity));
+        let mut check_bullet = |other_entity, bullet_entity| {
+            if bullets.get(bullet_entity).is_err() {
+                return;
+            }
+            if healths.get(other_entity).is_ok() {
+                contact_events.send(ContactEvent::HealthBullet(other_entity, bullet_entity));
+            } else if walls.get(other_entity).is_ok() {
+                contact_events.send(ContactEvent::BulletWall(bullet_entity, other_entity));
             }
         };
 
@@ -33,88 +71,474 @@ pub fn handle_intersections(
     }
 }
 
+pub fn track_ship_contacts(
+    mut collision_events: EventReader<TickCollisionEvent>,
+    mut ship_contacts: ResMut<ShipContacts>,
+    mut contact_events: EventWriter<ContactEvent>,
+    ships: Query<(), (With<Health>, Without<Bullet>)>,
+) {
+    for TickCollisionEvent(event) in collision_events.iter() {
+        let (entity1, entity2) = event.rigid_body_entities();
+        if ships.get(entity1).is_err() || ships.get(entity2).is_err() {
+            continue;
+        }
+        let pair = (entity1.min(entity2), entity1.max(entity2));
+        if event.is_started() {
+            contact_events.send(ContactEvent::Ram(pair.0, pair.1));
+            ship_contacts
+                .pairs
+                .insert(pair, Timer::from_seconds(RAM_COOLDOWN, true));
+        } else {
+            ship_contacts.pairs.remove(&pair);
+        }
+    }
+}
+
+pub fn repeat_ship_contacts(
+    timestep: Res<Timestep>,
+    mut ship_contacts: ResMut<ShipContacts>,
+    mut contact_events: EventWriter<ContactEvent>,
+    ships: Query<(), With<Health>>,
+) {
+    // Despawned units don't always stop their contacts.
+    ship_contacts
+        .pairs
+        .retain(|&(entity1, entity2), _| ships.get(entity1).is_ok() && ships.get(entity2).is_ok());
+    for (&(entity1, entity2), timer) in ship_contacts.pairs.iter_mut() {
+        if timer.tick(timestep.delta()).just_finished() {
+            contact_events.send(ContactEvent::Ram(entity1, entity2));
+        }
+    }
+}
+
+/// Damages health with what is left after [`mitigate_damage`], invulnerable units are spared.
+fn deal_damage(
+    (mut health, mut shield, armor, resistances, invulnerable): (
+        Mut<Health>,
+        Option<Mut<Shield>>,
+        Option<&Armor>,
+        Option<&Resistances>,
+        Option<&Invulnerable>,
+    ),
+    amount: u32,
+    damage_type: DamageType,
+    source: Option<Entity>,
+) {
+    // Units waiting to respawn stay around with no health.
+    if health.is_dead() || invulnerable.is_some() {
+        return;
+    }
+    let damage = mitigate_damage(
+        amount,
+        damage_type,
+        shield.as_deref_mut(),
+        armor,
+        resistances,
+    );
+    health.damage(damage, source);
+}
+
 pub fn handle_contacts(
     mut commands: Commands,
     mut contact_events: EventReader<ContactEvent>,
-    mut healths: Query<&mut Health>,
-    bullets: Query<&Bullet>,
+    mut explosion_events: EventWriter<ExplosionEvent>,
+    mut healths: Query<(
+        &mut Health,
+        Option<&mut Shield>,
+        Option<&Armor>,
+        Option<&Resistances>,
+        Option<&Invulnerable>,
+    )>,
+    mut bullets: Query<(
+        &Bullet,
+        &GlobalTransform,
+        Option<&Owner>,
+        Option<&Explosion>,
+        Option<&CollisionLayers>,
+        Option<&mut Pierce>,
+    )>,
+    mut bounces: Query<(&mut Bounce, &mut Velocity, &mut Transform)>,
+    walls: Query<&Wall>,
+    invulnerables: Query<(), With<Invulnerable>>,
+    contact_damages: Query<&ContactDamage>,
 ) {
+    // Bullets despawned during this run may still have contacts queued.
+    let mut spent = HashSet::new();
     for event in contact_events.iter() {
         match *event {
             ContactEvent::HealthBullet(health_entity, bullet_entity) => {
-                if let Some((mut health, bullet)) = healths
-                    .get_mut(health_entity)
+                // Bullets go through invulnerable units.
+                if spent.contains(&bullet_entity) || invulnerables.get(health_entity).is_ok() {
+                    continue;
+                }
+                let (bullet, transform, owner, explosion, layers, pierce) =
+                    match bullets.get_mut(bullet_entity) {
+                        Ok(bullet) => bullet,
+                        Err(_) => continue,
+                    };
+                if pierce
+                    .as_ref()
+                    .map_or(false, |pierce| pierce.hit.contains(&health_entity))
+                {
+                    continue;
+                }
+
+                if let Ok(target) = healths.get_mut(health_entity) {
+                    let owner = owner.map(|owner| owner.entity);
+                    deal_damage(target, bullet.damage, bullet.damage_type, owner);
+
+                    if let Some(explosion) = explosion {
+                        explosion_events.send(ExplosionEvent {
+                            position: transform.translation.truncate(),
+                            explosion: explosion.clone(),
+                            damage: bullet.damage,
+                            damage_type: bullet.damage_type,
+                            layers: layers.copied(),
+                            owner,
+                            ignore: Some(health_entity),
+                        });
+                    }
+                }
+
+                match pierce {
+                    Some(mut pierce) if pierce.remaining > 0 => {
+                        pierce.remaining -= 1;
+                        pierce.hit.push(health_entity);
+                    }
+                    _ => {
+                        spent.insert(bullet_entity);
+                        commands.entity(bullet_entity).despawn_recursive();
+                    }
+                }
+            }
+            ContactEvent::Ram(entity1, entity2) => {
+                for (target, source) in [(entity1, entity2), (entity2, entity1)] {
+                    if let Some((target, contact_damage)) = healths
+                        .get_mut(target)
+                        .ok()
+                        .zip(contact_damages.get(source).ok())
+                    {
+                        deal_damage(
+                            target,
+                            contact_damage.damage,
+                            contact_damage.damage_type,
+                            Some(source),
+                        );
+                    }
+                }
+            }
+            ContactEvent::BulletWall(bullet_entity, wall_entity) => {
+                if let Some(((mut bounce, mut velocity, mut transform), wall)) = bounces
+                    .get_mut(bullet_entity)
                     .ok()
-                    .zip(bullets.get(bullet_entity).ok())
+                    .zip(walls.get(wall_entity).ok())
                 {
-                    health.current = health.current.saturating_sub(bullet.damage);
+                    let normal = wall.normal.extend(0.0);
+                    let speed_into_wall = velocity.linear.dot(normal);
+                    // Bullets already moving away from the wall are leaving it after a bounce.
+                    if bounce.remaining == 0 || speed_into_wall >= 0.0 {
+                        continue;
+                    }
+                    bounce.remaining -= 1;
+                    velocity.linear -= 2.0 * speed_into_wall * normal;
+                    transform.rotation =
+                        Quat::from_rotation_arc(Vec3::Y, velocity.linear.normalize());
                 }
-                commands.entity(bullet_entity).despawn_recursive();
             }
         }
     }
 }
 
+/// Detonates explosive bullets despawned by their lifetime or range running out.
+pub fn detonate_expired_bullets(
+    mut explosion_events: EventWriter<ExplosionEvent>,
+    bullets: Query<(
+        &Bullet,
+        &Lifetime,
+        Option<&Projectile>,
+        &Explosion,
+        &GlobalTransform,
+        Option<&Owner>,
+        Option<&CollisionLayers>,
+    )>,
+) {
+    for (bullet, lifetime, projectile, explosion, transform, owner, layers) in bullets.iter() {
+        if lifetime.timer.just_finished() || projectile.map_or(false, Projectile::out_of_range) {
+            explosion_events.send(ExplosionEvent {
+                position: transform.translation.truncate(),
+                explosion: explosion.clone(),
+                damage: bullet.damage,
+                damage_type: bullet.damage_type,
+                layers: layers.copied(),
+                owner: owner.map(|owner| owner.entity),
+                ignore: None,
+            });
+        }
+    }
+}
+
+pub fn handle_explosions(
+    mut explosion_events: EventReader<ExplosionEvent>,
+    mut healths: Query<(
+        Entity,
+        &GlobalTransform,
+        Option<&CollisionLayers>,
+        &mut Health,
+        Option<&mut Shield>,
+        Option<&Armor>,
+        Option<&Resistances>,
+        Option<&Invulnerable>,
+    )>,
+) {
+    for event in explosion_events.iter() {
+        for (entity, transform, layers, health, shield, armor, resistances, invulnerable) in
+            healths.iter_mut()
+        {
+            if event.ignore == Some(entity) {
+                continue;
+            }
+            if let Some(explosion_layers) = event.layers {
+                if !layers.map_or(false, |layers| explosion_layers.interacts_with(*layers)) {
+                    continue;
+                }
+            }
+            let distance = transform.translation.truncate().distance(event.position);
+            if distance > event.explosion.radius {
+                continue;
+            }
+            let damage = event.explosion.damage_at(event.damage, distance);
+            deal_damage(
+                (health, shield, armor, resistances, invulnerable),
+                damage,
+                event.damage_type,
+                event.owner,
+            );
+        }
+    }
+}
+
+pub fn spawn_explosion_effects(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut explosion_events: EventReader<ExplosionEvent>,
+) {
+    for event in explosion_events.iter() {
+        let mut sprite = SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(1.0, 0.6, 0.2, 0.6),
+                custom_size: Some(Vec2::splat(event.explosion.radius * 2.0)),
+                ..default()
+            },
+            transform: Transform::from_translation(event.position.extend(1.0)),
+            ..default()
+        };
+        if let Some(effect) = &event.explosion.effect {
+            sprite.sprite.color = Color::WHITE;
+            sprite.texture = asset_server.load(effect.as_str());
+        }
+        commands
+            .spawn_bundle(sprite)
+            .insert(Lifetime {
+                timer: Timer::from_seconds(0.25, false),
+            })
+            .insert(Name::new("Explosion"));
+    }
+}
+
 pub fn despawn_dead(
     mut commands: Commands,
+    mut rng: ResMut<GameRng>,
     mut reward_events: EventWriter<RewardEvent>,
-    healths: Query<(Entity, &Health, Option<&Loot>), Changed<Health>>,
+    mut pickup_events: EventWriter<SpawnPickupEvent>,
+    mut healths: Query<
+        (
+            Entity,
+            &Health,
+            &GlobalTransform,
+            Option<&Loot>,
+            Option<&mut Lives>,
+        ),
+        Changed<Health>,
+    >,
 ) {
-    for (entity, health, loot) in healths.iter() {
+    for (entity, health, transform, loot, lives) in healths.iter_mut() {
         if health.is_dead() {
-            if let Some(loot) = loot {
-                reward_events.send(RewardEvent { score: loot.score });
+            if let Some(mut lives) = lives {
+                lives.remaining = lives.remaining.saturating_sub(1);
+                if lives.remaining > 0 {
+                    continue;
+                }
+            }
+            if let Some((loot, receiver)) = loot.zip(health.last_hit_by) {
+                reward_events.send(RewardEvent {
+                    receiver,
+                    score: loot.score,
+                });
+                if let Some(pickup) = loot.roll(&mut *rng) {
+                    pickup_events.send(SpawnPickupEvent {
+                        pickup: pickup.clone(),
+                        position: transform.translation.truncate(),
+                    });
+                }
             }
             commands.entity(entity).despawn_recursive();
         }
     }
 }
 
-pub fn update_cooldowns(time: Res<Time>, mut cooldowns: Query<&mut Cooldown>) {
+pub fn update_invulnerability(
+    mut commands: Commands,
+    timestep: Res<Timestep>,
+    mut units: Query<(Entity, &mut Invulnerable, Option<&mut Visibility>)>,
+) {
+    for (entity, mut invulnerable, visibility) in units.iter_mut() {
+        let finished = invulnerable.timer.tick(timestep.delta()).finished();
+        if finished {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+        if let Some(mut visibility) = visibility {
+            let blink = (invulnerable.timer.elapsed_secs() / BLINK_INTERVAL) as u32;
+            visibility.is_visible = finished || blink % 2 == 0;
+        }
+    }
+}
+
+pub fn reload_unit_stats(
+    mut commands: Commands,
+    mut asset_events: EventReader<AssetEvent<UnitPrefab>>,
+    unit_prefabs: Res<Assets<UnitPrefab>>,
+    units: Query<(Entity, &SourceUnit)>,
+) {
+    for event in asset_events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            if let Some(prefab) = unit_prefabs.get(handle) {
+                for (entity, _) in units.iter().filter(|(_, source)| source.0 == *handle) {
+                    commands.entity(entity).insert_bundle(prefab.stats());
+                }
+            }
+        }
+    }
+}
+
+pub fn scale_sprites(
+    images: Res<Assets<Image>>,
+    mut sprites: Query<(&mut Sprite, &Handle<Image>, &SpriteScale)>,
+) {
+    for (mut sprite, image, scale) in sprites.iter_mut() {
+        if let Some(image) = images.get(image) {
+            let size = image.size() * scale.0;
+            // Only written when different, so it isn't marked as changed every frame.
+            if sprite.custom_size != Some(size) {
+                sprite.custom_size = Some(size);
+            }
+        }
+    }
+}
+
+pub fn update_cooldowns(timestep: Res<Timestep>, mut cooldowns: Query<&mut Cooldown>) {
     for mut cooldown in cooldowns.iter_mut() {
-        cooldown.0.tick(time.delta());
+        cooldown.0.tick(timestep.delta());
     }
 }
 
+/// Equips the first weapon of the player's inventory into a free slot.
+#[cfg(feature = "debug")]
 pub fn test_equip_weapon(
-    keyboard_input: Res<Input<KeyCode>>,
+    tick_actions: Res<crate::actions::TickActions>,
     mut events: EventWriter<EquipWeaponEvent>,
-    players: Query<&Children, With<Player>>,
+    weapon_prefabs: Res<Assets<WeaponPrefab>>,
+    mut players: Query<(&Children, &mut WeaponInventory), With<crate::player::Player>>,
     free_slots: Query<Entity, (With<WeaponSlot>, Without<Weapon>)>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::E) {
-        for children in players.iter() {
-            if let Some(slot_entity) = children
+    if tick_actions
+        .common
+        .just_pressed(crate::actions::Action::EquipWeapon)
+    {
+        for (children, mut inventory) in players.iter_mut() {
+            let slot_entity = children
                 .iter()
-                .find_map(|child| free_slots.get(*child).ok())
-            {
+                .find_map(|child| free_slots.get(*child).ok());
+            let weapon = inventory
+                .weapons
+                .first()
+                .and_then(|weapon| weapon_prefabs.get(weapon))
+                .cloned();
+            if let Some((slot_entity, weapon)) = slot_entity.zip(weapon) {
+                inventory.weapons.remove(0);
                 events.send(EquipWeaponEvent {
                     slot_entity,
-                    weapon: WeaponPrefab {
-                        damage: Damage(1),
-                        cooldown: 0.2.into(),
-                        bullet: BulletPrefab {
-                            size: Vec2::new(5.0, 12.0),
-                            body: "images/bullets/bullet2.png".into(),
-                        }
-                        .into(),
-                    },
+                    weapon,
                 });
             }
         }
     }
 }
 
+/// Removes every weapon component from the slot, the slot itself is kept.
+fn clear_weapon_slot(commands: &mut Commands, slot_entity: Entity, beam: Option<&Beam>) {
+    if let Some(sprite) = beam.and_then(|beam| beam.sprite) {
+        commands.entity(sprite).despawn_recursive();
+    }
+    commands.entity(slot_entity).remove_bundle::<(
+        Weapon,
+        EquippedWeapon,
+        Damage,
+        DamageType,
+        Cooldown,
+        FirePattern,
+        Heat,
+        Magazine,
+        Beam,
+        Burst,
+        WeaponLevel,
+    )>();
+}
+
+/// Puts the weapon equipped in the slot back into its unit's inventory, if the unit has one.
+fn stash_weapon(
+    weapon_prefabs: &mut Assets<WeaponPrefab>,
+    equipped: Option<&EquippedWeapon>,
+    level: Option<&WeaponLevel>,
+    parent: Option<&Parent>,
+    inventories: &mut Query<&mut WeaponInventory>,
+) {
+    if let Some((equipped, mut inventory)) =
+        equipped.zip(parent.and_then(|parent| inventories.get_mut(parent.0).ok()))
+    {
+        let weapon = WeaponPrefab {
+            level: level.map_or(0, |level| level.current),
+            ..equipped.0.clone()
+        };
+        inventory.weapons.push(weapon_prefabs.add(weapon));
+    }
+}
+
 pub fn equip_weapon(
     mut commands: Commands,
     mut events: EventReader<EquipWeaponEvent>,
-    transforms: Query<&Transform>,
+    mut weapon_prefabs: ResMut<Assets<WeaponPrefab>>,
+    slots: Query<(
+        Option<&Transform>,
+        Option<&EquippedWeapon>,
+        Option<&WeaponLevel>,
+        Option<&Beam>,
+        Option<&Parent>,
+    )>,
+    mut inventories: Query<&mut WeaponInventory>,
 ) {
     for event in events.iter() {
-        let transform = transforms
-            .get(event.slot_entity)
-            .cloned()
-            .unwrap_or_default();
+        let (transform, equipped, level, beam, parent) =
+            slots.get(event.slot_entity).unwrap_or_default();
+        stash_weapon(
+            &mut weapon_prefabs,
+            equipped,
+            level,
+            parent,
+            &mut inventories,
+        );
+        clear_weapon_slot(&mut commands, event.slot_entity, beam);
+
+        let transform = transform.cloned().unwrap_or_default();
         commands
             .entity(event.slot_entity)
             .insert_bundle(TransformBundle::from_transform(transform))
@@ -122,20 +546,203 @@ pub fn equip_weapon(
     }
 }
 
+pub fn unequip_weapon(
+    mut commands: Commands,
+    mut events: EventReader<UnequipWeaponEvent>,
+    mut weapon_prefabs: ResMut<Assets<WeaponPrefab>>,
+    slots: Query<
+        (
+            Option<&EquippedWeapon>,
+            Option<&WeaponLevel>,
+            Option<&Beam>,
+            Option<&Parent>,
+        ),
+        With<WeaponSlot>,
+    >,
+    mut inventories: Query<&mut WeaponInventory>,
+) {
+    for &UnequipWeaponEvent { slot_entity } in events.iter() {
+        if let Ok((equipped, level, beam, parent)) = slots.get(slot_entity) {
+            stash_weapon(
+                &mut weapon_prefabs,
+                equipped,
+                level,
+                parent,
+                &mut inventories,
+            );
+            clear_weapon_slot(&mut commands, slot_entity, beam);
+        }
+    }
+}
+
+pub fn swap_weapons(
+    mut commands: Commands,
+    mut events: EventReader<SwapWeaponsEvent>,
+    slots: Query<(Option<&EquippedWeapon>, Option<&WeaponLevel>, Option<&Beam>), With<WeaponSlot>>,
+) {
+    for &SwapWeaponsEvent {
+        first_slot,
+        second_slot,
+    } in events.iter()
+    {
+        if let Some(((first, first_level, first_beam), (second, second_level, second_beam))) =
+            slots.get(first_slot).ok().zip(slots.get(second_slot).ok())
+        {
+            clear_weapon_slot(&mut commands, first_slot, first_beam);
+            clear_weapon_slot(&mut commands, second_slot, second_beam);
+            if let Some(EquippedWeapon(weapon)) = second {
+                commands.entity(first_slot).apply_prefab(WeaponAtLevel {
+                    weapon: weapon.clone(),
+                    level: second_level.map_or(0, |level| level.current),
+                });
+            }
+            if let Some(EquippedWeapon(weapon)) = first {
+                commands.entity(second_slot).apply_prefab(WeaponAtLevel {
+                    weapon: weapon.clone(),
+                    level: first_level.map_or(0, |level| level.current),
+                });
+            }
+        }
+    }
+}
+
+pub fn upgrade_weapons(
+    mut commands: Commands,
+    mut events: EventReader<UpgradeWeaponEvent>,
+    weapons: Query<(&EquippedWeapon, &WeaponLevel)>,
+) {
+    for &UpgradeWeaponEvent { slot_entity } in events.iter() {
+        if let Ok((EquippedWeapon(weapon), level)) = weapons.get(slot_entity) {
+            if level.current < level.max {
+                commands.entity(slot_entity).apply_prefab(WeaponAtLevel {
+                    weapon: weapon.clone(),
+                    level: level.current + 1,
+                });
+            }
+        }
+    }
+}
+
 pub fn handle_shoot_events(
+    mut commands: Commands,
     mut shoot_events: EventReader<ShootEvent>,
     mut spawn_bullet_events: EventWriter<SpawnBulletEvent>,
-    children: Query<&Children>,
-    weapons: Query<(Entity, &Cooldown), With<Weapon>>,
+    bullets: Res<Assets<BulletPrefab>>,
+    mut shooters: Query<(&Children, Option<&mut NextBarrel>)>,
+    patterns: Query<&FirePattern>,
+    mut weapons: Query<
+        (
+            &Weapon,
+            &mut Cooldown,
+            Option<&FirePattern>,
+            Option<&mut Heat>,
+            Option<&mut Magazine>,
+        ),
+        Without<Burst>,
+    >,
 ) {
     for &ShootEvent { shooter } in shoot_events.iter() {
-        if let Ok(children) = children.get(shooter) {
-            for (weapon, _) in children
+        if let Ok((children, next_barrel)) = shooters.get_mut(shooter) {
+            let barrels = children
                 .iter()
-                .filter_map(|child| weapons.get(*child).ok())
-                .filter(|(_, cooldown)| cooldown.0.finished())
-            {
-                spawn_bullet_events.send(SpawnBulletEvent { weapon, shooter });
+                .copied()
+                .filter(|&weapon| {
+                    patterns
+                        .get(weapon)
+                        .map_or(false, |pattern| pattern.alternate)
+                })
+                .collect::<Vec<_>>();
+            let turn = next_barrel.as_ref().map_or(0, |next| next.0);
+            let barrel = barrels.get(turn % barrels.len().max(1)).copied();
+            let mut barrel_fired = false;
+
+            for &weapon in children.iter() {
+                if let Ok((weapon_data, mut cooldown, pattern, heat, magazine)) =
+                    weapons.get_mut(weapon)
+                {
+                    let alternate = pattern.map_or(false, |pattern| pattern.alternate);
+                    if !cooldown.0.finished()
+                        || (alternate && barrel != Some(weapon))
+                        || heat.as_ref().map_or(false, |heat| heat.overheated)
+                        || magazine
+                            .as_ref()
+                            .map_or(false, |magazine| magazine.is_reloading())
+                    {
+                        continue;
+                    }
+                    // Nothing is fired until the bullet is loaded, keeping the cooldown ready.
+                    let loaded = weapon_data
+                        .bullet
+                        .as_ref()
+                        .map_or(false, |bullet| bullets.get(bullet).is_some());
+                    if !loaded {
+                        continue;
+                    }
+                    cooldown.0.reset();
+                    barrel_fired |= alternate;
+                    // A burst counts as a single shot.
+                    if let Some(mut heat) = heat {
+                        let per_shot = heat.per_shot;
+                        heat.add(per_shot);
+                    }
+                    if let Some(mut magazine) = magazine {
+                        magazine.rounds -= 1;
+                        if magazine.is_reloading() {
+                            magazine.reload.reset();
+                        }
+                    }
+                    spawn_bullet_events.send(SpawnBulletEvent { weapon, shooter });
+
+                    if let Some(pattern) = pattern.filter(|pattern| pattern.burst > 1) {
+                        if pattern.burst_interval > 0.0 {
+                            commands.entity(weapon).insert(Burst {
+                                shooter,
+                                remaining: pattern.burst - 1,
+                                timer: Timer::from_seconds(pattern.burst_interval, true),
+                            });
+                        } else {
+                            // A repeating timer can't run without a duration, volleys go together.
+                            for _ in 1..pattern.burst {
+                                spawn_bullet_events.send(SpawnBulletEvent { weapon, shooter });
+                            }
+                        }
+                    }
+                }
+            }
+
+            // The other barrels wait for the cooldown of the one that fired.
+            if barrel_fired {
+                for &other in barrels.iter().filter(|&&other| Some(other) != barrel) {
+                    if let Ok((_, mut cooldown, ..)) = weapons.get_mut(other) {
+                        cooldown.0.reset();
+                    }
+                }
+                match next_barrel {
+                    Some(mut next) => next.0 = turn + 1,
+                    None => {
+                        commands.entity(shooter).insert(NextBarrel(turn + 1));
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn update_bursts(
+    mut commands: Commands,
+    timestep: Res<Timestep>,
+    mut bursts: Query<(Entity, &mut Burst)>,
+    mut spawn_bullet_events: EventWriter<SpawnBulletEvent>,
+) {
+    for (weapon, mut burst) in bursts.iter_mut() {
+        if burst.timer.tick(timestep.delta()).just_finished() {
+            spawn_bullet_events.send(SpawnBulletEvent {
+                weapon,
+                shooter: burst.shooter,
+            });
+            burst.remaining -= 1;
+            if burst.remaining == 0 {
+                commands.entity(weapon).remove::<Burst>();
             }
         }
     }
@@ -144,31 +751,61 @@ pub fn handle_shoot_events(
 pub fn spawn_bullets(
     mut commands: Commands,
     mut events: EventReader<SpawnBulletEvent>,
-    mut weapons: Query<(&Weapon, &mut Cooldown, &GlobalTransform, Option<&Damage>), With<Weapon>>,
+    mut rng: ResMut<GameRng>,
+    weapons: Query<(
+        &Weapon,
+        &GlobalTransform,
+        Option<&Damage>,
+        Option<&DamageType>,
+        Option<&FirePattern>,
+    )>,
     collision_layers: Query<&CollisionLayers>,
+    bullets: Res<Assets<BulletPrefab>>,
 ) {
     for &SpawnBulletEvent {
         weapon: weapon_entity,
         shooter,
     } in events.iter()
     {
-        if let Ok((weapon, mut cooldown, transform, damage)) = weapons.get_mut(weapon_entity) {
-            cooldown.0.reset();
+        if let Ok((weapon, transform, damage, damage_type, pattern)) = weapons.get(weapon_entity) {
+            let bullet = match weapon
+                .bullet
+                .as_ref()
+                .and_then(|bullet| bullets.get(bullet))
+            {
+                Some(bullet) => bullet,
+                None => continue,
+            };
             let damage = damage.map(|damage| damage.0).unwrap_or(0);
-            let bullet_speed = 300.0;
-            let bullet_velocity = transform.rotation.mul_vec3(Vec3::Y * bullet_speed);
+            let damage_type = damage_type.copied().unwrap_or_default();
+            let pattern = pattern.cloned().unwrap_or_default();
+
+            for angle in pattern.angles() {
+                let deviation = if pattern.inaccuracy > 0.0 {
+                    rng.gen_range(-pattern.inaccuracy..=pattern.inaccuracy)
+                } else {
+                    0.0
+                };
+                let mut bullet_transform: Transform = (*transform).into();
+                bullet_transform.rotation *=
+                    Quat::from_rotation_z((angle + deviation).to_radians());
+                let bullet_velocity = bullet_transform.rotation.mul_vec3(Vec3::Y * bullet.speed);
 
-            let bullet_entity = commands
-                .spawn()
-                .insert_bundle(TransformBundle::from_transform((*transform).into()))
-                .insert(weapon.bullet.clone())
-                .insert(Bullet { damage })
-                .insert(Velocity::from_linear(bullet_velocity))
-                .insert(Owner { entity: shooter })
-                .id();
+                let bullet_entity = commands
+                    .spawn()
+                    .insert_bundle(TransformBundle::from_transform(bullet_transform))
+                    .insert(weapon.bullet.clone())
+                    .insert(Bullet {
+                        damage,
+                        damage_type,
+                    })
+                    .insert(Velocity::from_linear(bullet_velocity))
+                    .insert(Owner { entity: shooter })
+                    .id();
 
-            if let Ok(layers) = collision_layers.get(shooter) {
-                commands.entity(bullet_entity).insert(*layers);
+                if let Ok(layers) = collision_layers.get(shooter) {
+                    commands.entity(bullet_entity).insert(*layers);
+                }
             }
         }
     }
@@ -176,11 +813,369 @@ pub fn spawn_bullets(
 
 pub fn apply_score_reward(
     mut reward_events: EventReader<RewardEvent>,
-    mut players: Query<&mut Scores, With<Player>>,
+    mut scores: Query<(&mut Scores, Option<&ScoreMultiplier>)>,
 ) {
-    for RewardEvent { score } in reward_events.iter() {
-        for mut scores in players.iter_mut() {
-            scores.amount += score;
+    for &RewardEvent { receiver, score } in reward_events.iter() {
+        if let Ok((mut scores, multiplier)) = scores.get_mut(receiver) {
+            scores.amount += multiplier.map_or(score, |multiplier| {
+                (score as f32 * multiplier.factor).round() as u32
+            });
         }
     }
 }
+
+pub fn move_projectiles(
+    mut commands: Commands,
+    timestep: Res<Timestep>,
+    mut projectiles: Query<(Entity, &mut Projectile, &mut Velocity)>,
+) {
+    let delta = timestep.delta_seconds();
+    for (entity, mut projectile, mut velocity) in projectiles.iter_mut() {
+        let speed = (velocity.linear.length() + projectile.acceleration * delta).max(0.0);
+        velocity.linear = velocity.linear.normalize_or_zero() * speed;
+        projectile.traveled += speed * delta;
+
+        if projectile.out_of_range() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+pub fn steer_homing_bullets(
+    timestep: Res<Timestep>,
+    mut bullets: Query<(&mut Homing, &mut Velocity, &mut Transform, &CollisionLayers)>,
+    targets: Query<
+        (Entity, &GlobalTransform, &CollisionLayers),
+        (With<Health>, Without<Respawning>),
+    >,
+) {
+    for (mut homing, mut velocity, mut transform, bullet_layers) in bullets.iter_mut() {
+        let position = transform.translation.truncate();
+        let is_target = |layers: &CollisionLayers| {
+            bullet_layers.interacts_with(*layers)
+                && homing
+                    .layer
+                    .map_or(true, |layer| layers.contains_group(layer))
+        };
+
+        // Targets which are gone or respawning are dropped for the nearest remaining one.
+        let target_position = match homing.target.and_then(|target| targets.get(target).ok()) {
+            Some((_, target_transform, _)) => Some(target_transform.translation.truncate()),
+            None => {
+                let nearest = targets
+                    .iter()
+                    .filter(|(_, _, layers)| is_target(layers))
+                    .map(|(entity, target_transform, _)| {
+                        (entity, target_transform.translation.truncate())
+                    })
+                    .filter(|(_, target)| target.distance(position) <= homing.radius)
+                    .min_by(|(_, a), (_, b)| {
+                        a.distance_squared(position)
+                            .total_cmp(&b.distance_squared(position))
+                    });
+                homing.target = nearest.map(|(entity, _)| entity);
+                nearest.map(|(_, target)| target)
+            }
+        };
+
+        let target_position = match target_position {
+            Some(target_position) => target_position,
+            None => continue,
+        };
+
+        let direction = velocity.linear.truncate();
+        let speed = direction.length();
+        let desired = target_position - position;
+        if speed == 0.0 || desired == Vec2::ZERO {
+            continue;
+        }
+        let max_turn = homing.turn_rate.to_radians() * timestep.delta_seconds();
+        let turn = direction.angle_between(desired).clamp(-max_turn, max_turn);
+        let rotation = Quat::from_rotation_z(turn);
+
+        velocity.linear = rotation.mul_vec3(velocity.linear);
+        transform.rotation = rotation * transform.rotation;
+    }
+}
+
+pub fn cool_weapons(timestep: Res<Timestep>, mut heats: Query<&mut Heat>) {
+    for mut heat in heats.iter_mut() {
+        let cooling = heat.cooling_rate * timestep.delta_seconds();
+        heat.cool(cooling);
+    }
+}
+
+pub fn reload_magazines(timestep: Res<Timestep>, mut magazines: Query<&mut Magazine>) {
+    for mut magazine in magazines.iter_mut() {
+        if magazine.is_reloading() && magazine.reload.tick(timestep.delta()).finished() {
+            magazine.rounds = magazine.capacity;
+        }
+    }
+}
+
+pub fn fire_beams(
+    mut shoot_events: EventReader<ShootEvent>,
+    children: Query<&Children>,
+    mut beams: Query<(&mut Beam, Option<&Heat>)>,
+) {
+    for &ShootEvent { shooter } in shoot_events.iter() {
+        if let Ok(children) = children.get(shooter) {
+            for &weapon in children.iter() {
+                if let Ok((mut beam, heat)) = beams.get_mut(weapon) {
+                    if heat.map_or(true, |heat| !heat.overheated) {
+                        beam.shooter = Some(shooter);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn update_beams(
+    mut commands: Commands,
+    timestep: Res<Timestep>,
+    physics_world: PhysicsWorld,
+    mut beams: Query<(
+        Entity,
+        &mut Beam,
+        &GlobalTransform,
+        Option<&DamageType>,
+        Option<&mut Heat>,
+    )>,
+    collision_layers: Query<&CollisionLayers>,
+    mut healths: Query<(
+        &mut Health,
+        Option<&mut Shield>,
+        Option<&Armor>,
+        Option<&Resistances>,
+        Option<&Invulnerable>,
+    )>,
+    mut sprites: Query<(&mut Sprite, &mut Transform, &mut Visibility)>,
+) {
+    let delta = timestep.delta_seconds();
+    for (weapon, mut beam, transform, damage_type, heat) in beams.iter_mut() {
+        let mut beam_length = 0.0;
+        if let Some(shooter) = beam.shooter.take() {
+            if let Some(mut heat) = heat {
+                heat.add(beam.heat_per_second * delta);
+            }
+
+            let start = transform.translation;
+            let ray = transform.rotation.mul_vec3(Vec3::Y * beam.length);
+            let layers = collision_layers.get(shooter).copied().unwrap_or_default();
+            let hit = physics_world.ray_cast_with_filter(start, ray, true, layers, |entity| {
+                healths.get(entity).is_ok()
+            });
+
+            beam_length = match hit {
+                Some(hit) => {
+                    beam.damage_progress += beam.damage_per_second * delta;
+                    let damage = beam.damage_progress.floor();
+                    beam.damage_progress -= damage;
+                    if damage > 0.0 {
+                        if let Ok(target) = healths.get_mut(hit.entity) {
+                            let damage_type = damage_type.copied().unwrap_or_default();
+                            deal_damage(target, damage as u32, damage_type, Some(shooter));
+                        }
+                    }
+                    start.distance(hit.collision_point)
+                }
+                None => beam.length,
+            };
+        }
+
+        let size = Vec2::new(beam.width, beam_length);
+        let sprite_transform = Transform::from_xyz(0.0, beam_length / 2.0, -0.1);
+        match beam.sprite.and_then(|sprite| sprites.get_mut(sprite).ok()) {
+            Some((mut sprite, mut transform, mut visibility)) => {
+                sprite.custom_size = Some(size);
+                *transform = sprite_transform;
+                visibility.is_visible = beam_length > 0.0;
+            }
+            None => {
+                let sprite = commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite {
+                            color: Color::rgba(0.4, 0.9, 1.0, 0.8),
+                            custom_size: Some(size),
+                            ..default()
+                        },
+                        transform: sprite_transform,
+                        visibility: Visibility {
+                            is_visible: beam_length > 0.0,
+                        },
+                        ..default()
+                    })
+                    .insert(Name::new("Beam"))
+                    .id();
+                commands.entity(weapon).add_child(sprite);
+                beam.sprite = Some(sprite);
+            }
+        }
+    }
+}
+
+pub fn regenerate_shields(timestep: Res<Timestep>, mut shields: Query<&mut Shield>) {
+    for mut shield in shields.iter_mut() {
+        if shield.current >= shield.max || !shield.regen_delay.tick(timestep.delta()).finished() {
+            continue;
+        }
+        shield.regen_progress += shield.regen_rate * timestep.delta_seconds();
+        let regenerated = shield.regen_progress.floor();
+        shield.regen_progress -= regenerated;
+        shield.current = (shield.current + regenerated as u32).min(shield.max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::asset::AssetPlugin;
+
+    use super::*;
+    use crate::prefab::Prefab;
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .add_asset::<WeaponPrefab>()
+            .add_event::<UnequipWeaponEvent>()
+            .add_event::<SwapWeaponsEvent>()
+            .add_system(unequip_weapon)
+            .add_system(swap_weapons);
+        app
+    }
+
+    fn weapon(name: &str, damage: u32) -> WeaponPrefab {
+        WeaponPrefab {
+            name: Some(name.into()),
+            price: None,
+            bullet: None,
+            beam: None,
+            damage: Damage(damage),
+            damage_type: DamageType::default(),
+            cooldown: Cooldown::default(),
+            heat: None,
+            magazine: None,
+            pattern: FirePattern::default(),
+            upgrades: Vec::new(),
+            level: 0,
+        }
+    }
+
+    /// Spawns a unit with an empty inventory and a slot for each weapon, returns them.
+    fn spawn_unit(app: &mut App, weapons: &[Option<WeaponPrefab>]) -> (Entity, Vec<Entity>) {
+        let unit = app.world.spawn().insert(WeaponInventory::default()).id();
+        let slots = weapons
+            .iter()
+            .map(|weapon| {
+                let slot = app.world.spawn().insert(WeaponSlot).id();
+                if let Some(weapon) = weapon {
+                    weapon.apply(slot, &mut app.world);
+                }
+                slot
+            })
+            .collect::<Vec<_>>();
+        app.world.entity_mut(unit).push_children(&slots);
+        (unit, slots)
+    }
+
+    #[test]
+    fn unequip_returns_the_weapon_to_the_inventory() {
+        let mut app = app();
+        let (unit, slots) = spawn_unit(&mut app, &[Some(weapon("Laser", 3))]);
+
+        app.world
+            .resource_mut::<Events<UnequipWeaponEvent>>()
+            .send(UnequipWeaponEvent {
+                slot_entity: slots[0],
+            });
+        app.update();
+
+        let slot = app.world.entity(slots[0]);
+        assert!(slot.contains::<WeaponSlot>());
+        assert!(!slot.contains::<Weapon>());
+        assert!(!slot.contains::<Damage>());
+        assert!(!slot.contains::<Cooldown>());
+        let inventory = app.world.get::<WeaponInventory>(unit).unwrap();
+        assert_eq!(inventory.weapons.len(), 1);
+        let stashed = app
+            .world
+            .resource::<Assets<WeaponPrefab>>()
+            .get(&inventory.weapons[0])
+            .unwrap();
+        assert_eq!(stashed.name.as_deref(), Some("Laser"));
+    }
+
+    #[test]
+    fn bursts_without_interval_fire_every_volley_at_once() {
+        let mut app = app();
+        app.add_asset::<BulletPrefab>()
+            .add_event::<ShootEvent>()
+            .add_event::<SpawnBulletEvent>()
+            .add_system(handle_shoot_events);
+        let bullet = app
+            .world
+            .resource_mut::<Assets<BulletPrefab>>()
+            .add(BulletPrefab {
+                size: Vec2::splat(4.0),
+                body: String::new(),
+                speed: 300.0,
+                lifetime: 1.0,
+                acceleration: 0.0,
+                max_range: None,
+                pierce: 0,
+                bounce: 0,
+                homing: None,
+                explosion: None,
+            });
+        let mut cooldown = Timer::from_seconds(0.0, false);
+        cooldown.tick(Duration::ZERO);
+        let weapon = app
+            .world
+            .spawn()
+            .insert(Weapon {
+                bullet: Some(bullet),
+            })
+            .insert(Cooldown(cooldown))
+            .insert(FirePattern {
+                burst: 3,
+                burst_interval: 0.0,
+                ..default()
+            })
+            .id();
+        let shooter = app.world.spawn().push_children(&[weapon]).id();
+
+        app.world
+            .resource_mut::<Events<ShootEvent>>()
+            .send(ShootEvent { shooter });
+        app.update();
+
+        let events = app.world.resource::<Events<SpawnBulletEvent>>();
+        assert_eq!(events.get_reader().iter(events).count(), 3);
+        assert!(!app.world.entity(weapon).contains::<Burst>());
+    }
+
+    #[test]
+    fn swap_exchanges_the_weapons_of_two_slots() {
+        let mut app = app();
+        let (_, slots) = spawn_unit(
+            &mut app,
+            &[Some(weapon("Laser", 3)), Some(weapon("Cannon", 8))],
+        );
+
+        app.world
+            .resource_mut::<Events<SwapWeaponsEvent>>()
+            .send(SwapWeaponsEvent {
+                first_slot: slots[0],
+                second_slot: slots[1],
+            });
+        app.update();
+
+        let damage = |slot| app.world.get::<Damage>(slot).map(|damage| damage.0);
+        assert_eq!(damage(slots[0]), Some(8));
+        assert_eq!(damage(slots[1]), Some(3));
+    }
+}
@@ -0,0 +1,309 @@
+#![allow(clippy::type_complexity)]
+
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use crate::{
+    actions::{Action, ActionMap, Bindings, BINDINGS_PATH},
+    despawn_with,
+    loading::FontAssets,
+    main_menu::NORMAL_BUTTON,
+    player::MAX_PLAYERS,
+    states::GameState,
+};
+
+/// Set of bindings shown on the screen.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ControlsPage {
+    Solo,
+    Player(usize),
+    Common,
+}
+
+impl Default for ControlsPage {
+    fn default() -> Self {
+        Self::Solo
+    }
+}
+
+impl ControlsPage {
+    fn next(self) -> Self {
+        match self {
+            ControlsPage::Solo => ControlsPage::Player(0),
+            ControlsPage::Player(index) if index + 1 < MAX_PLAYERS => {
+                ControlsPage::Player(index + 1)
+            }
+            ControlsPage::Player(_) => ControlsPage::Common,
+            ControlsPage::Common => ControlsPage::Solo,
+        }
+    }
+
+    fn title(self) -> String {
+        match self {
+            ControlsPage::Solo => "Solo".into(),
+            ControlsPage::Player(index) => format!("Player {}", index + 1),
+            ControlsPage::Common => "Common".into(),
+        }
+    }
+
+    fn actions(self) -> &'static [Action] {
+        match self {
+            ControlsPage::Solo | ControlsPage::Player(_) => &Action::PLAYER,
+            ControlsPage::Common => Action::COMMON,
+        }
+    }
+
+    fn map(self, bindings: &Bindings) -> Option<&ActionMap> {
+        match self {
+            ControlsPage::Solo => Some(&bindings.solo),
+            ControlsPage::Player(index) => bindings.players.get(index),
+            ControlsPage::Common => Some(&bindings.common),
+        }
+    }
+
+    fn map_mut(self, bindings: &mut Bindings) -> &mut ActionMap {
+        match self {
+            ControlsPage::Solo => &mut bindings.solo,
+            ControlsPage::Player(index) => {
+                if bindings.players.len() <= index {
+                    bindings.players.resize_with(index + 1, ActionMap::default);
+                }
+                &mut bindings.players[index]
+            }
+            ControlsPage::Common => &mut bindings.common,
+        }
+    }
+}
+
+/// Action waiting for its new key.
+#[derive(Default)]
+struct Rebinding(Option<Action>);
+
+#[derive(Component, Clone, Copy)]
+enum ControlsButton {
+    Bind(Action),
+    NextPage,
+    Reset,
+    Back,
+}
+
+#[derive(Component)]
+struct ControlsUITag;
+
+/// Lists the bindings of each page, clicking an action binds it to the next pressed key.
+/// Bindings are saved when leaving the screen.
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ControlsPage>()
+            .init_resource::<Rebinding>()
+            .add_system_set(SystemSet::on_enter(GameState::Controls).with_system(reset_page))
+            .add_system_set(
+                SystemSet::on_update(GameState::Controls)
+                    .with_system(handle_button_click)
+                    .with_system(capture_key)
+                    .with_system(handle_keyboard)
+                    .with_system(refresh_ui),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Controls)
+                    .with_system(save_bindings)
+                    .with_system(despawn_with::<ControlsUITag>),
+            );
+    }
+}
+
+fn reset_page(mut page: ResMut<ControlsPage>, mut rebinding: ResMut<Rebinding>) {
+    *page = ControlsPage::default();
+    rebinding.0 = None;
+}
+
+fn save_bindings(bindings: Res<Bindings>) {
+    match bindings.save(Path::new(BINDINGS_PATH)) {
+        Ok(()) => info!("Bindings saved to {}", BINDINGS_PATH),
+        Err(err) => error!("Unable to save bindings: {}", err),
+    }
+}
+
+fn handle_button_click(
+    interaction_query: Query<(&Interaction, &ControlsButton), (Changed<Interaction>, With<Button>)>,
+    mut bindings: ResMut<Bindings>,
+    mut page: ResMut<ControlsPage>,
+    mut rebinding: ResMut<Rebinding>,
+    mut state: ResMut<State<GameState>>,
+) {
+    for (interaction, tag) in interaction_query.iter() {
+        if let Interaction::Clicked = *interaction {
+            match *tag {
+                ControlsButton::Bind(action) => rebinding.0 = Some(action),
+                ControlsButton::NextPage => {
+                    *page = page.next();
+                    rebinding.0 = None;
+                }
+                ControlsButton::Reset => {
+                    *bindings = Bindings::default();
+                    rebinding.0 = None;
+                }
+                ControlsButton::Back => state
+                    .set(GameState::MainMenu)
+                    .expect("Unable to change state to MainMenu"),
+            }
+        }
+    }
+}
+
+/// Replaces every key of the action being rebound with the pressed one.
+fn capture_key(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut bindings: ResMut<Bindings>,
+    page: Res<ControlsPage>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    let action = match rebinding.0 {
+        Some(action) => action,
+        None => return,
+    };
+    if let Some(&key) = keyboard_input.get_just_pressed().next() {
+        // Keeps the key from also triggering the action it is bound to.
+        keyboard_input.reset(key);
+        page.map_mut(&mut bindings).0.insert(action, vec![key]);
+        rebinding.0 = None;
+    }
+}
+
+fn handle_keyboard(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    bindings: Res<Bindings>,
+    rebinding: Res<Rebinding>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let actions = &bindings.common;
+    if rebinding.0.is_none() && actions.just_pressed(Action::Back, &keyboard_input) {
+        // The main menu exits the game on back.
+        actions.reset(Action::Back, &mut keyboard_input);
+        state
+            .set(GameState::MainMenu)
+            .expect("Unable to change state to MainMenu");
+    }
+}
+
+/// Respawns the screen whenever what it shows changes.
+fn refresh_ui(
+    mut commands: Commands,
+    fonts: Res<FontAssets>,
+    bindings: Res<Bindings>,
+    page: Res<ControlsPage>,
+    rebinding: Res<Rebinding>,
+    ui: Query<Entity, With<ControlsUITag>>,
+) {
+    if !ui.is_empty() && !bindings.is_changed() && !page.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+    for entity in ui.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let font = fonts.font.clone();
+    let map = page.map(&bindings);
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(ControlsUITag)
+        .insert(Name::new("Controls UI"))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    format!("CONTROLS - {}", page.title()),
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 56.0,
+                        color: Color::ORANGE,
+                    },
+                    Default::default(),
+                ),
+                ..default()
+            });
+            for &action in page.actions() {
+                let keys = if rebinding.0 == Some(action) {
+                    "Press a key...".to_string()
+                } else {
+                    map.map_or(&[][..], |map| map.keys(action))
+                        .iter()
+                        .map(|key| format!("{:?}", key))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                spawn_button(
+                    parent,
+                    &format!("{:?}: {}", action, keys),
+                    ControlsButton::Bind(action),
+                    Size::new(Val::Px(400.0), Val::Px(40.0)),
+                    font.clone(),
+                );
+            }
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        margin: Rect::all(Val::Px(20.0)),
+                        ..default()
+                    },
+                    color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|row| {
+                    let size = Size::new(Val::Px(150.0), Val::Px(50.0));
+                    spawn_button(row, "Next", ControlsButton::NextPage, size, font.clone());
+                    spawn_button(row, "Reset", ControlsButton::Reset, size, font.clone());
+                    spawn_button(row, "Back", ControlsButton::Back, size, font.clone());
+                });
+        });
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    text: &str,
+    tag: ControlsButton,
+    size: Size<Val>,
+    font: Handle<Font>,
+) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size,
+                margin: Rect::all(Val::Px(5.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: NORMAL_BUTTON.into(),
+            ..default()
+        })
+        .with_children(|button| {
+            button.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    text,
+                    TextStyle {
+                        font,
+                        font_size: 24.0,
+                        color: Color::BLACK,
+                    },
+                    Default::default(),
+                ),
+                ..default()
+            });
+        })
+        .insert(tag);
+}
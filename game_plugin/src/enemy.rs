@@ -1,14 +1,24 @@
+#![allow(clippy::type_complexity)]
+
 use bevy::prelude::*;
 #[cfg(feature = "debug")]
 use bevy_inspector_egui::{Inspectable, RegisterInspectable};
 use heron::prelude::*;
-use rand::{prelude::random, seq::SliceRandom};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "debug")]
 use crate::{
-    combat::{ShootEvent, UnitPrefab},
+    actions::{Action, TickActions},
+    replay::REPLAY_INPUT_LABEL,
+};
+use crate::{
+    combat::{Engine, ShootEvent, SourceUnit, UnitPrefab, SHOOT_LABEL},
     despawn_with,
-    player::Player,
+    player::{Player, Respawning},
+    rng::GameRng,
     states::GameState,
+    timestep::{AddTickEvent, Timestep, FIXED_UPDATE},
     PhysicsLayers,
 };
 
@@ -17,6 +27,7 @@ use crate::{
 pub struct Enemy;
 
 #[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Dir {
     Left,
     Right,
@@ -38,7 +49,7 @@ impl Default for Dir {
 }
 
 #[cfg_attr(feature = "debug", derive(Inspectable))]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Serialize, Deserialize)]
 pub enum RotationDir {
     Clockwise,
     CounterClockwise,
@@ -51,25 +62,43 @@ impl Default for RotationDir {
 }
 
 #[cfg_attr(feature = "debug", derive(Inspectable))]
-#[derive(Component)]
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub enum Movement {
     Static,
     Horizontal {
         min: f32,
         max: f32,
+        #[serde(default)]
         current_dir: Dir,
     },
     Chase {
+        #[serde(skip)]
         target: Option<Entity>,
+        /// Units per second, chasers are slower than their `Engine` to be dodgeable.
+        #[serde(default = "default_chase_speed")]
+        speed: f32,
     },
     Circle {
         center: Vec2,
         radius: f32,
+        #[serde(default)]
         rotation_dir: RotationDir,
+        #[serde(default)]
         current_angle: f32,
+        /// Radians per second, independent of the radius.
+        #[serde(default = "default_angular_speed")]
+        angular_speed: f32,
     },
 }
 
+fn default_chase_speed() -> f32 {
+    40.0
+}
+
+fn default_angular_speed() -> f32 {
+    1.0
+}
+
 impl Default for Movement {
     fn default() -> Self {
         Self::Static
@@ -83,13 +112,25 @@ impl Movement {
             radius,
             rotation_dir,
             current_angle: 0.0,
+            angular_speed: default_angular_speed(),
+        }
+    }
+
+    pub fn chase(target: Option<Entity>) -> Self {
+        Self::Chase {
+            target,
+            speed: default_chase_speed(),
         }
     }
 }
 
-pub struct SpawnEnemyEvent;
+pub struct SpawnEnemyEvent {
+    pub unit: Handle<UnitPrefab>,
+    pub position: Option<Vec2>,
+    pub movement: Option<Movement>,
+}
 
-const COUNT_ENEMIES_LABEL: &str = "count_enemies";
+pub const SPAWN_ENEMY_LABEL: &str = "spawn_enemy";
 
 pub struct EnemyPlugin;
 
@@ -99,65 +140,49 @@ impl Plugin for EnemyPlugin {
         app.register_inspectable::<Enemy>()
             .register_inspectable::<Dir>()
             .register_inspectable::<Movement>();
-        app.add_event::<SpawnEnemyEvent>()
-            .add_system_set(
+        app.add_tick_event::<SpawnEnemyEvent>()
+            .add_system_set_to_stage(
+                FIXED_UPDATE,
                 SystemSet::on_update(GameState::Gameplay)
-                    .with_system(count_enemies.label(COUNT_ENEMIES_LABEL))
-                    .with_system(spawn_enemy.after(COUNT_ENEMIES_LABEL))
+                    // Draws from `GameRng` before the bullets do.
+                    .with_system(spawn_enemy.label(SPAWN_ENEMY_LABEL).before(SHOOT_LABEL))
                     .with_system(movement)
-                    .with_system(enemy_shoot)
-                    .with_system(test_chase),
+                    .with_system(enemy_shoot.label(SHOOT_LABEL)),
             )
             .add_system_set(
                 SystemSet::on_enter(GameState::Countdown).with_system(despawn_with::<Enemy>),
             );
-    }
-}
 
-fn count_enemies(mut events: EventWriter<SpawnEnemyEvent>, enemies: Query<&Enemy>) {
-    let enemy_count = enemies.iter().count();
-    if enemy_count < 3 {
-        for _ in 0..(3 - enemy_count) {
-            events.send(SpawnEnemyEvent);
-        }
+        #[cfg(feature = "debug")]
+        app.add_system_set_to_stage(
+            FIXED_UPDATE,
+            SystemSet::on_update(GameState::Gameplay)
+                .with_system(test_chase.after(REPLAY_INPUT_LABEL)),
+        );
     }
 }
 
 fn spawn_enemy(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
     mut events: EventReader<SpawnEnemyEvent>,
+    mut rng: ResMut<GameRng>,
+    players: Query<(Entity, &Transform), (With<Player>, Without<Respawning>)>,
 ) {
-    let mut spawn = || {
-        let mut rng = rand::thread_rng();
-        // TODO
-        static ENEMIES: [&str; 2] = ["predator", "dragon"];
-        let choosen_enemy = ENEMIES.choose(&mut rng).cloned().unwrap();
-        let choosen_enemy = format!("units/{}.unit.ron", choosen_enemy);
-        let unit_handle: Handle<UnitPrefab> = asset_server.get_handle(choosen_enemy);
-
-        let position = Vec3::new(
-            random::<f32>() * 400.0 - 200.0,
-            random::<f32>() * 200.0,
-            0.0,
-        );
+    for event in events.iter() {
+        let position = event.position.unwrap_or_else(|| {
+            Vec2::new(rng.gen::<f32>() * 400.0 - 200.0, rng.gen::<f32>() * 200.0)
+        });
 
-        let movement = if random::<bool>() {
-            Movement::Horizontal {
-                min: random::<f32>() * 300.0 - 300.0,
-                max: random::<f32>() * 300.0,
-                current_dir: Dir::Left,
-            }
-        } else {
-            Movement::circle(
-                Vec2::new(random::<f32>() * 100.0, random::<f32>() * 100.0),
-                random::<f32>() * 100.0 + 10.0,
-                if random() {
-                    RotationDir::Clockwise
-                } else {
-                    RotationDir::CounterClockwise
-                },
-            )
+        let movement = match event.movement.clone() {
+            Some(Movement::Chase {
+                target: None,
+                speed,
+            }) => Movement::Chase {
+                speed,
+                target: nearest_player(players.iter(), position),
+            },
+            Some(movement) => movement,
+            None => random_movement(&mut *rng),
         };
 
         commands
@@ -166,50 +191,79 @@ fn spawn_enemy(
             .insert(RotationConstraints::lock())
             .insert(Enemy)
             .insert(movement)
-            .insert(unit_handle)
+            .insert(SourceUnit(event.unit.clone()))
+            .insert(event.unit.clone())
             .insert_bundle(TransformBundle::from_transform(
-                Transform::from_translation(position)
+                Transform::from_translation(position.extend(0.0))
                     .with_rotation(Quat::from_rotation_z(180.0f32.to_radians())),
             ))
             .insert(CollisionLayers::new(
                 PhysicsLayers::Enemy,
                 PhysicsLayers::Player,
             ));
-    };
+    }
+}
 
-    for _ in events.iter() {
-        spawn();
+fn nearest_player<'a>(
+    players: impl Iterator<Item = (Entity, &'a Transform)>,
+    position: Vec2,
+) -> Option<Entity> {
+    players
+        .min_by(|(_, a), (_, b)| {
+            let a = a.translation.truncate().distance_squared(position);
+            let b = b.translation.truncate().distance_squared(position);
+            a.total_cmp(&b)
+        })
+        .map(|(entity, _)| entity)
+}
+
+fn random_movement(rng: &mut impl Rng) -> Movement {
+    if rng.gen::<bool>() {
+        Movement::Horizontal {
+            min: rng.gen::<f32>() * 300.0 - 300.0,
+            max: rng.gen::<f32>() * 300.0,
+            current_dir: Dir::Left,
+        }
+    } else {
+        Movement::circle(
+            Vec2::new(rng.gen::<f32>() * 100.0, rng.gen::<f32>() * 100.0),
+            rng.gen::<f32>() * 100.0 + 10.0,
+            if rng.gen() {
+                RotationDir::Clockwise
+            } else {
+                RotationDir::CounterClockwise
+            },
+        )
     }
 }
 
+#[cfg(feature = "debug")]
 fn test_chase(
     mut commands: Commands,
-    input: Res<Input<KeyCode>>,
-    players: Query<Entity, With<Player>>,
-    movements: Query<(Entity, &Movement)>,
+    tick_actions: Res<TickActions>,
+    players: Query<(Entity, &Transform), (With<Player>, Without<Respawning>)>,
+    movements: Query<(Entity, &Movement, &Transform)>,
 ) {
-    if !input.just_pressed(KeyCode::C) {
+    if !tick_actions.common.just_pressed(Action::TestChase) {
         return;
     }
-    if let Some((player_entity, chasing_entity)) = players.get_single().ok().zip(
-        movements
-            .iter()
-            .find(|(_, m)| !matches!(m, Movement::Chase { .. }))
-            .map(|(e, _)| e),
-    ) {
-        commands.entity(chasing_entity).insert(Movement::Chase {
-            target: Some(player_entity),
-        });
+    if let Some((chasing_entity, _, transform)) = movements
+        .iter()
+        .find(|(_, m, _)| !matches!(m, Movement::Chase { .. }))
+    {
+        let target = nearest_player(players.iter(), transform.translation.truncate());
+        commands
+            .entity(chasing_entity)
+            .insert(Movement::chase(target));
     }
 }
 
 fn movement(
-    time: Res<Time>,
-    mut enemies: Query<(&mut Movement, &mut Transform)>,
-    // TODO: use query set, because the target may have Movement component
-    transforms: Query<&Transform, Without<Movement>>,
+    timestep: Res<Timestep>,
+    mut enemies: Query<(&mut Movement, &mut Transform, &Engine)>,
+    players: Query<(Entity, &Transform), (With<Player>, Without<Respawning>, Without<Movement>)>,
 ) {
-    for (mut movement, mut transform) in enemies.iter_mut() {
+    for (mut movement, mut transform, engine) in enemies.iter_mut() {
         match *movement {
             Movement::Horizontal {
                 min,
@@ -222,16 +276,23 @@ fn movement(
                     *current_dir = Dir::Left;
                 }
                 transform.translation +=
-                    current_dir.as_f32() * 100.0 * time.delta_seconds() * Vec3::X;
+                    current_dir.as_f32() * engine.speed * timestep.delta_seconds() * Vec3::X;
             }
-            Movement::Chase { target } => {
-                if let Some(target_transform) =
-                    target.and_then(|target| transforms.get(target).ok())
+            Movement::Chase {
+                ref mut target,
+                speed,
+            } => {
+                // Eliminated players are despawned and respawning ones out of the game,
+                // the nearest remaining one is chased instead.
+                if target.map_or(true, |target| players.get(target).is_err()) {
+                    *target = nearest_player(players.iter(), transform.translation.truncate());
+                }
+                if let Some((_, target_transform)) =
+                    target.and_then(|target| players.get(target).ok())
                 {
                     let target_position = target_transform.translation;
                     let dir = (target_position - transform.translation).normalize_or_zero();
-                    let speed = 40.0;
-                    transform.translation += speed * dir * time.delta_seconds();
+                    transform.translation += speed * dir * timestep.delta_seconds();
                 }
             }
             Movement::Circle {
@@ -239,15 +300,15 @@ fn movement(
                 ref mut current_angle,
                 radius,
                 rotation_dir,
+                angular_speed,
             } => {
                 let x = center.x + radius * current_angle.cos();
                 let y = center.y + radius * current_angle.sin();
-                let angular_speed = 1.0;
                 let dir = match rotation_dir {
                     RotationDir::Clockwise => 1.0,
                     RotationDir::CounterClockwise => -1.0,
                 };
-                *current_angle += dir * time.delta_seconds() * angular_speed;
+                *current_angle += dir * timestep.delta_seconds() * angular_speed;
                 transform.translation = Vec3::new(x, y, 0.0);
             }
             Movement::Static => {}
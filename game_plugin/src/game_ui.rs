@@ -1,15 +1,19 @@
 use bevy::prelude::*;
 
 use crate::{
-    combat::Scores,
+    combat::{Lives, Scores},
     loading::FontAssets,
     main_menu::{hide_ui, show_ui},
-    player::Player,
+    player::{Player, PlayerCount, PlayerIndex, MAX_PLAYERS},
     states::GameState,
 };
 
+/// HUD entries of the player with the same index.
 #[derive(Component)]
-pub struct PlayerScoresText;
+pub struct PlayerScoresText(usize);
+
+#[derive(Component)]
+pub struct PlayerLivesText(usize);
 
 #[derive(Component)]
 pub struct GameUITag;
@@ -22,20 +26,87 @@ impl Plugin for GameUiPlugin {
             SystemSet::on_enter(GameState::Gameplay).with_system(show_ui::<GameUITag>),
         )
         .add_system_set(SystemSet::on_exit(GameState::Gameplay).with_system(hide_ui::<GameUITag>))
+        .add_system_set(SystemSet::on_enter(GameState::Countdown).with_system(clear_hud))
         .add_system_set(SystemSet::on_exit(GameState::Loading).with_system(setup_ui))
-        .add_system(display_scores);
+        .add_system(display_scores)
+        .add_system(display_lives);
+    }
+}
+
+/// Players are only told apart when there are several of them.
+fn player_label(index: &PlayerIndex, player_count: &PlayerCount) -> String {
+    if player_count.0 > 1 {
+        format!("P{} ", index.0 + 1)
+    } else {
+        String::new()
+    }
+}
+
+fn clear_hud(mut texts: Query<&mut Text, Or<(With<PlayerScoresText>, With<PlayerLivesText>)>>) {
+    for mut text in texts.iter_mut() {
+        text.sections[0].value.clear();
     }
 }
 
 fn display_scores(
-    mut text: Query<&mut Text, With<PlayerScoresText>>,
-    scores: Query<&Scores, (Changed<Scores>, With<Player>)>,
+    mut texts: Query<(&mut Text, &PlayerScoresText)>,
+    scores: Query<(&PlayerIndex, &Scores), (Changed<Scores>, With<Player>)>,
+    player_count: Res<PlayerCount>,
+) {
+    for (index, scores) in scores.iter() {
+        for (mut text, _) in texts.iter_mut().filter(|(_, tag)| tag.0 == index.0) {
+            text.sections[0].value = format!(
+                "{}Score: {}",
+                player_label(index, &player_count),
+                scores.amount
+            );
+        }
+    }
+}
+
+fn display_lives(
+    mut texts: Query<(&mut Text, &PlayerLivesText)>,
+    lives: Query<(&PlayerIndex, &Lives), (Changed<Lives>, With<Player>)>,
+    player_count: Res<PlayerCount>,
 ) {
-    if let Some((scores, mut text)) = scores.get_single().ok().zip(text.get_single_mut().ok()) {
-        text.sections[0].value = format!("Score: {}", scores.amount);
+    for (index, lives) in lives.iter() {
+        for (mut text, _) in texts.iter_mut().filter(|(_, tag)| tag.0 == index.0) {
+            text.sections[0].value = format!(
+                "{}Lives: {}",
+                player_label(index, &player_count),
+                lives.remaining
+            );
+        }
     }
 }
 
+fn spawn_text(parent: &mut ChildBuilder, font: Handle<Font>, tag: impl Component) {
+    parent
+        .spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font,
+                    font_size: 26.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Left,
+                },
+            ),
+            style: Style {
+                margin: Rect {
+                    right: Val::Px(20.0),
+                    ..default()
+                },
+                ..default()
+            },
+            ..default()
+        })
+        .insert(tag);
+}
+
 fn setup_ui(mut commands: Commands, fonts: Res<FontAssets>) {
     commands
         .spawn_bundle(NodeBundle {
@@ -56,7 +127,7 @@ fn setup_ui(mut commands: Commands, fonts: Res<FontAssets>) {
                 .spawn_bundle(NodeBundle {
                     style: Style {
                         size: Size::new(Val::Percent(100.0), Val::Px(32.0)),
-                        flex_direction: FlexDirection::RowReverse,
+                        justify_content: JustifyContent::SpaceBetween,
                         align_items: AlignItems::FlexEnd,
                         padding: Rect::all(Val::Px(10.0)),
                         ..default()
@@ -65,23 +136,17 @@ fn setup_ui(mut commands: Commands, fonts: Res<FontAssets>) {
                     ..default()
                 })
                 .with_children(|parent| {
-                    parent
-                        .spawn_bundle(TextBundle {
-                            text: Text::with_section(
-                                "Score: 0",
-                                TextStyle {
-                                    font: fonts.font.clone(),
-                                    font_size: 26.0,
-                                    color: Color::WHITE,
-                                },
-                                TextAlignment {
-                                    vertical: VerticalAlign::Center,
-                                    horizontal: HorizontalAlign::Right,
-                                },
-                            ),
-                            ..default()
-                        })
-                        .insert(PlayerScoresText);
+                    for index in 0..MAX_PLAYERS {
+                        parent
+                            .spawn_bundle(NodeBundle {
+                                color: Color::NONE.into(),
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                spawn_text(parent, fonts.font.clone(), PlayerScoresText(index));
+                                spawn_text(parent, fonts.font.clone(), PlayerLivesText(index));
+                            });
+                    }
                 });
         });
 }
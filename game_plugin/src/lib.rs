@@ -1,69 +1,210 @@
+mod actions;
 mod combat;
+mod controls;
 mod countdown;
 mod enemy;
 mod game_over;
 mod game_ui;
 mod loading;
 mod main_menu;
+mod pause;
+mod pickup;
 mod player;
 mod prefab;
+mod replay;
+mod rng;
+mod ship_select;
+mod shop;
 mod states;
+mod timestep;
+mod wave;
 
-use bevy::prelude::*;
+use std::time::Duration;
+
+use bevy::{
+    app::ScheduleRunnerSettings, asset::AssetPlugin, hierarchy::HierarchyPlugin,
+    input::InputPlugin, prelude::*,
+};
 #[cfg(feature = "debug")]
 use bevy_inspector_egui::{Inspectable, RegisterInspectable, WorldInspectorPlugin};
-use heron::prelude::*;
+use heron::{prelude::*, rapier_plugin::RapierPlugin, PhysicsSteps, PhysicsTime};
+use serde::{Deserialize, Serialize};
 
+use actions::ActionsPlugin;
 use combat::CombatPlugin;
+use controls::ControlsPlugin;
 use countdown::CountdownPlugin;
 use enemy::EnemyPlugin;
 use game_over::GameOverPlugin;
 use game_ui::GameUiPlugin;
 use loading::LoadingPlugin;
 use main_menu::MainMenuPlugin;
+use pause::PausePlugin;
+use pickup::PickupPlugin;
 use player::PlayerPlugin;
-use states::GameState;
+pub use replay::ReplayMode;
+use replay::{Replay, ReplayPlugin};
+use rng::RngPlugin;
+use ship_select::ShipSelectPlugin;
+use shop::ShopPlugin;
+pub use states::GameState;
+use timestep::{on_tick, AddTickEvent, Timestep, TimestepPlugin, DEFAULT_TICK_RATE, FIXED_UPDATE};
+use wave::WavePlugin;
 
-#[derive(PhysicsLayer)]
+#[derive(PhysicsLayer, Clone, Copy, Serialize, Deserialize)]
 pub enum PhysicsLayers {
     Player,
     Enemy,
+    Pickup,
 }
 
+/// Collisions of the last physics step, [`FIXED_UPDATE`] systems should read them
+/// instead of `CollisionEvent`, which is dropped on frames without a tick.
+pub struct TickCollisionEvent(pub CollisionEvent);
+
 #[derive(Component)]
 #[cfg_attr(feature = "debug", derive(Inspectable))]
 pub struct Owner {
     pub entity: Entity,
 }
 
+/// Systems reacting to an expiring [`Lifetime`] should run after it,
+/// while the entity still exists.
+pub const TRACK_LIFETIME_LABEL: &str = "track_lifetime";
+
+/// Bounds of the play area, `normal` points inward.
+#[derive(Component)]
+pub struct Wall {
+    pub normal: Vec2,
+}
+
 #[derive(Component)]
 pub struct Lifetime {
     pub timer: Timer,
 }
 
-pub struct GamePlugin;
+#[derive(Default)]
+pub struct GamePlugin {
+    headless: bool,
+    seed: Option<u64>,
+    tick_rate: Option<f64>,
+    replay: Option<ReplayMode>,
+}
+
+impl GamePlugin {
+    /// Runs the game without a window: rendering and UI plugins are not added,
+    /// so UI entities are still spawned but never laid out or drawn.
+    /// Every `App::update` runs exactly one gameplay tick, use it to drive the game from code.
+    pub fn headless() -> Self {
+        Self {
+            headless: true,
+            ..default()
+        }
+    }
+
+    /// Makes every run reproducible, a random seed is picked otherwise.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Sets how many times per second the gameplay simulation is updated.
+    pub fn with_tick_rate(mut self, tick_rate: f64) -> Self {
+        self.tick_rate = Some(tick_rate);
+        self
+    }
+
+    /// Records gameplay input to a file, or plays it back instead of the live keyboard.
+    /// Playback uses the seed and tick rate stored in the replay.
+    pub fn with_replay(mut self, mode: ReplayMode) -> Self {
+        self.replay = Some(mode);
+        self
+    }
+}
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app.add_state(GameState::Loading)
-            .add_plugins(DefaultPlugins);
+        app.add_state(GameState::Loading);
+
+        let mut seed = self.seed.unwrap_or_else(rand::random);
+        let mut tick_rate = self.tick_rate.unwrap_or(DEFAULT_TICK_RATE);
+        let replay_plugin = match &self.replay {
+            Some(ReplayMode::Record(path)) => ReplayPlugin::Record(path.clone()),
+            Some(ReplayMode::Playback(path)) => {
+                let replay = Replay::load(path).expect("Unable to load replay");
+                seed = replay.seed;
+                tick_rate = replay.tick_rate;
+                ReplayPlugin::Playback(replay)
+            }
+            None => ReplayPlugin::Disabled,
+        };
+        let tick_duration = Duration::from_secs_f64(1.0 / tick_rate);
+
+        if self.headless {
+            // Every update runs a single tick, paced so the game still runs in real time.
+            app.insert_resource(ScheduleRunnerSettings::run_loop(tick_duration))
+                .init_resource::<WindowDescriptor>()
+                .add_plugins(MinimalPlugins)
+                .add_plugin(TransformPlugin)
+                .add_plugin(HierarchyPlugin)
+                .add_plugin(InputPlugin)
+                .add_plugin(AssetPlugin);
+        } else {
+            app.add_plugins(DefaultPlugins)
+                .add_startup_system(spawn_cameras);
+
+            #[cfg(feature = "debug")]
+            app.add_plugin(WorldInspectorPlugin::default())
+                .register_inspectable::<Owner>();
+        }
 
-        #[cfg(feature = "debug")]
-        app.add_plugin(WorldInspectorPlugin::default())
-            .register_inspectable::<Owner>();
+        app.add_plugin(TimestepPlugin {
+            tick_rate,
+            tick_per_update: self.headless,
+        });
+        app.insert_resource(PhysicsSteps::every_frame(tick_duration));
+        if self.headless {
+            // Without the debug renderer of `PhysicsPlugin`, which needs the render plugins.
+            app.add_plugin(RapierPlugin);
+        } else {
+            app.add_plugin(PhysicsPlugin::default());
+        }
+        // Physics steps once after every tick, so contacts don't depend on the frame rate.
+        app.stage(heron::stage::ROOT, |schedule: &mut Schedule| {
+            schedule.set_run_criteria(IntoSystem::into_system(on_tick))
+        })
+        .add_tick_event::<TickCollisionEvent>()
+        .add_system_to_stage(CoreStage::PostUpdate, relay_collisions);
 
-        app.add_plugin(PhysicsPlugin::default())
-            .add_plugin(LoadingPlugin)
+        app.add_plugin(RngPlugin { seed })
+            .add_plugin(ActionsPlugin)
+            .add_plugin(replay_plugin)
+            .add_plugin(LoadingPlugin {
+                headless: self.headless,
+            })
             .add_plugin(MainMenuPlugin)
+            .add_plugin(ShipSelectPlugin)
+            .add_plugin(ControlsPlugin)
             .add_plugin(CountdownPlugin)
             .add_plugin(GameOverPlugin)
             .add_plugin(PlayerPlugin)
             .add_plugin(CombatPlugin)
             .add_plugin(EnemyPlugin)
+            .add_plugin(WavePlugin)
+            .add_plugin(PickupPlugin)
+            .add_plugin(ShopPlugin)
+            .add_plugin(PausePlugin)
             .add_plugin(GameUiPlugin)
             .add_startup_system(spawn_bounds)
-            .add_startup_system(spawn_cameras)
-            .add_system(track_lifetime);
+            .add_system_set_to_stage(
+                FIXED_UPDATE,
+                SystemSet::on_update(GameState::Gameplay)
+                    .with_system(track_lifetime.label(TRACK_LIFETIME_LABEL)),
+            )
+            // Lifetimes only run during gameplay, leftovers of the previous game are removed.
+            .add_system_set(
+                SystemSet::on_enter(GameState::Countdown).with_system(despawn_with::<Lifetime>),
+            );
     }
 
     fn name(&self) -> &str {
@@ -71,6 +212,24 @@ impl Plugin for GamePlugin {
     }
 }
 
+/// Freezes the physics simulation while a state is pushed on top of `GameState::Gameplay`.
+pub fn pause_physics(mut physics_time: ResMut<PhysicsTime>) {
+    physics_time.pause();
+}
+
+pub fn resume_physics(mut physics_time: ResMut<PhysicsTime>) {
+    physics_time.resume();
+}
+
+fn relay_collisions(
+    mut collision_events: EventReader<CollisionEvent>,
+    mut tick_events: EventWriter<TickCollisionEvent>,
+) {
+    for event in collision_events.iter() {
+        tick_events.send(TickCollisionEvent(event.clone()));
+    }
+}
+
 pub fn despawn_with<T: Component>(mut commands: Commands, query: Query<Entity, With<T>>) {
     for entity in query.iter() {
         commands.entity(entity).despawn_recursive();
@@ -91,7 +250,8 @@ fn spawn_bounds(mut commands: Commands, window: Res<WindowDescriptor>) {
         Vec2::new(0.0, (-window.height + thickness) / 2.0),
         Vec2::new(0.0, (window.height - thickness) / 2.0),
     ];
-    for (size, position) in sizes.into_iter().zip(positions.iter()) {
+    let normals = vec![Vec2::X, -Vec2::X, Vec2::Y, -Vec2::Y];
+    for ((size, position), normal) in sizes.into_iter().zip(positions.iter()).zip(normals) {
         commands
             .spawn_bundle(TransformBundle {
                 local: Transform::from_translation(position.extend(0.0)),
@@ -106,6 +266,7 @@ fn spawn_bounds(mut commands: Commands, window: Res<WindowDescriptor>) {
                 friction: 0.0,
                 ..default()
             })
+            .insert(Wall { normal })
             .insert(Name::new("Wall"));
     }
 }
@@ -118,10 +279,10 @@ fn spawn_cameras(mut commands: Commands) {
 pub fn track_lifetime(
     mut cmd: Commands,
     mut query: Query<(Entity, &mut Lifetime)>,
-    time: ResMut<Time>,
+    timestep: Res<Timestep>,
 ) {
     for (entity, mut lifetime) in query.iter_mut() {
-        if lifetime.timer.tick(time.delta()).just_finished() {
+        if lifetime.timer.tick(timestep.delta()).just_finished() {
             cmd.entity(entity).despawn();
         }
     }
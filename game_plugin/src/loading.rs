@@ -3,18 +3,29 @@ use bevy_asset_loader::{AssetCollection, AssetLoader};
 
 use crate::{
     combat::{BulletPrefab, UnitPrefab, WeaponPrefab},
+    shop::ShopCatalog,
     states::GameState,
+    wave::{LevelPrefab, WavePrefab},
 };
 
-pub struct LoadingPlugin;
+pub struct LoadingPlugin {
+    pub headless: bool,
+}
 
 impl Plugin for LoadingPlugin {
     fn build(&self, app: &mut App) {
-        AssetLoader::new(GameState::Loading)
+        let mut loader = AssetLoader::new(GameState::Loading)
             .continue_to_state(GameState::MainMenu)
-            .with_collection::<FontAssets>()
-            .with_collection::<AssetsFolder>()
-            .build(app);
+            .with_collection::<AssetsFolder>();
+        if self.headless {
+            // Fonts can't be loaded without the text plugin, UI is spawned with the default handle
+            app.insert_resource(FontAssets {
+                font: Handle::default(),
+            });
+        } else {
+            loader = loader.with_collection::<FontAssets>();
+        }
+        loader.build(app);
         app.add_system_set(SystemSet::on_enter(GameState::Loading).with_system(
             |asset_server: ResMut<AssetServer>| {
                 println!("Loading...");
@@ -40,4 +51,10 @@ pub struct AssetsFolder {
     pub weapons: Vec<Handle<WeaponPrefab>>,
     #[asset(path = "bullets", folder(typed))]
     pub bullets: Vec<Handle<BulletPrefab>>,
+    #[asset(path = "waves", folder(typed))]
+    pub waves: Vec<Handle<WavePrefab>>,
+    #[asset(path = "levels", folder(typed))]
+    pub levels: Vec<Handle<LevelPrefab>>,
+    #[asset(path = "shop/main.shop.ron")]
+    pub shop: Handle<ShopCatalog>,
 }
@@ -4,12 +4,19 @@ use bevy::{app::AppExit, prelude::*};
 #[cfg(feature = "debug")]
 use bevy_inspector_egui::{Inspectable, RegisterInspectable};
 
-use crate::{loading::FontAssets, states::GameState};
+use crate::{
+    actions::{Action, Bindings},
+    loading::FontAssets,
+    player::{PlayerCount, MAX_PLAYERS},
+    states::GameState,
+};
 
 #[derive(Component)]
 #[cfg_attr(feature = "debug", derive(Inspectable))]
 enum MenuButtonTag {
     Start,
+    Coop,
+    Controls,
     Exit,
 }
 
@@ -67,15 +74,27 @@ fn button_color_system(
     }
 }
 
+fn open_ship_select(state: &mut State<GameState>, player_count: &mut PlayerCount, count: usize) {
+    player_count.0 = count;
+    state
+        .set(GameState::ShipSelect)
+        .expect("Unable to change state to ShipSelect");
+}
+
 fn handle_button_click(
     interaction_query: Query<(&Interaction, &MenuButtonTag), (Changed<Interaction>, With<Button>)>,
-    mut start_game_events: EventWriter<StartGameEvent>,
+    mut state: ResMut<State<GameState>>,
+    mut player_count: ResMut<PlayerCount>,
     mut exit_events: EventWriter<AppExit>,
 ) {
     for (interaction, tag) in interaction_query.iter() {
         if let Interaction::Clicked = *interaction {
             match tag {
-                MenuButtonTag::Start => start_game_events.send(StartGameEvent),
+                MenuButtonTag::Start => open_ship_select(&mut state, &mut player_count, 1),
+                MenuButtonTag::Coop => open_ship_select(&mut state, &mut player_count, MAX_PLAYERS),
+                MenuButtonTag::Controls => state
+                    .set(GameState::Controls)
+                    .expect("Unable to change state to Controls"),
                 MenuButtonTag::Exit => exit_events.send(AppExit),
             }
         }
@@ -83,15 +102,20 @@ fn handle_button_click(
 }
 
 fn handle_keyboard(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut start_game_events: EventWriter<StartGameEvent>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    bindings: Res<Bindings>,
+    mut state: ResMut<State<GameState>>,
+    mut player_count: ResMut<PlayerCount>,
     mut exit_events: EventWriter<AppExit>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::Return) {
-        start_game_events.send(StartGameEvent);
+    let actions = &bindings.common;
+    if actions.just_pressed(Action::Confirm, &keyboard_input) {
+        // The ship select screen starts the game on confirm.
+        actions.reset(Action::Confirm, &mut keyboard_input);
+        open_ship_select(&mut state, &mut player_count, 1);
     }
 
-    if keyboard_input.just_pressed(KeyCode::Escape) {
+    if actions.just_pressed(Action::Back, &keyboard_input) {
         exit_events.send(AppExit);
     }
 }
@@ -110,9 +134,10 @@ pub fn hide_ui<T: Component>(mut ui: Query<&mut Style, With<T>>) {
 
 fn handle_start_game(mut events: EventReader<StartGameEvent>, mut state: ResMut<State<GameState>>) {
     if events.iter().next().is_some() {
-        state
-            .set(GameState::Countdown)
-            .expect("Unable to change state to Gameplay");
+        // Backing out of the ship selection on the same frame may have queued the main menu.
+        if let Err(err) = state.set(GameState::Countdown) {
+            warn!("Unable to change state to Countdown: {}", err);
+        }
     }
 }
 
@@ -134,6 +159,8 @@ fn setup_main_menu(mut commands: Commands, fonts: Res<FontAssets>) {
         })
         .with_children(|parent| {
             spawn_button(parent, "Start", MenuButtonTag::Start, font.clone());
+            spawn_button(parent, "Co-op", MenuButtonTag::Coop, font.clone());
+            spawn_button(parent, "Controls", MenuButtonTag::Controls, font.clone());
             spawn_button(parent, "Exit", MenuButtonTag::Exit, font);
         })
         .insert(MainMenuTag)
@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+
+use crate::{
+    actions::{Action, Bindings},
+    despawn_with,
+    loading::FontAssets,
+    pause_physics, resume_physics,
+    states::GameState,
+};
+
+#[derive(Component)]
+struct PauseUITag;
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_update(GameState::Gameplay).with_system(pause_game))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Paused)
+                    .with_system(pause_physics)
+                    .with_system(setup_ui),
+            )
+            .add_system_set(SystemSet::on_update(GameState::Paused).with_system(resume_game))
+            .add_system_set(
+                SystemSet::on_exit(GameState::Paused)
+                    .with_system(resume_physics)
+                    .with_system(despawn_with::<PauseUITag>),
+            );
+    }
+}
+
+fn pause_game(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    bindings: Res<Bindings>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let actions = &bindings.common;
+    if actions.just_pressed(Action::Pause, &keyboard_input) {
+        // The paused state resumes the game on pause.
+        actions.reset(Action::Pause, &mut keyboard_input);
+        // The shop or game over may already be queued by this frame.
+        if let Err(err) = state.push(GameState::Paused) {
+            warn!("Unable to push Paused state: {}", err);
+        }
+    }
+}
+
+fn resume_game(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    bindings: Res<Bindings>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let actions = &bindings.common;
+    if actions.just_pressed(Action::Pause, &keyboard_input)
+        || actions.just_pressed(Action::Confirm, &keyboard_input)
+    {
+        actions.reset(Action::Pause, &mut keyboard_input);
+        actions.reset(Action::Confirm, &mut keyboard_input);
+        if let Err(err) = state.pop() {
+            warn!("Unable to pop Paused state: {}", err);
+        }
+    }
+}
+
+fn setup_ui(mut commands: Commands, fonts: Res<FontAssets>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+            ..default()
+        })
+        .insert(PauseUITag)
+        .insert(Name::new("Pause UI"))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "PAUSED",
+                    TextStyle {
+                        font: fonts.font.clone(),
+                        font_size: 96.0,
+                        color: Color::ORANGE,
+                    },
+                    Default::default(),
+                ),
+                ..default()
+            });
+        });
+}
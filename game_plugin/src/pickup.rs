@@ -0,0 +1,287 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+#[cfg(feature = "debug")]
+use bevy_inspector_egui::{Inspectable, RegisterInspectable};
+use heron::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{
+        EquipWeaponEvent, EquippedWeapon, Health, Shield, UpgradeWeaponEvent, Weapon,
+        WeaponInventory, WeaponLevel, WeaponPrefab, WeaponSlot, DESPAWN_DEAD_LABEL,
+    },
+    despawn_with,
+    player::Player,
+    prefab::PrefabHandle,
+    states::GameState,
+    timestep::{AddTickEvent, Timestep, FIXED_UPDATE},
+    Lifetime, PhysicsLayers, TickCollisionEvent,
+};
+
+const PICKUP_SIZE: f32 = 14.0;
+const PICKUP_SPEED: f32 = 40.0;
+const PICKUP_LIFETIME: f32 = 8.0;
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub enum Pickup {
+    Repair(u32),
+    /// Raises maximum health, the added health is restored as well.
+    MaxHealth(u32),
+    /// Recharges the shield, units without one get a shield that doesn't regenerate.
+    Shield(u32),
+    /// Equipped into the first free weapon slot, or put into the inventory when all are taken.
+    /// Upgrades an equipped weapon of the same name instead, until it is at its last level.
+    Weapon(PrefabHandle<WeaponPrefab>),
+    ScoreMultiplier {
+        factor: f32,
+        duration: f32,
+    },
+}
+
+impl Pickup {
+    fn color(&self) -> Color {
+        match self {
+            Pickup::Repair(_) => Color::GREEN,
+            Pickup::MaxHealth(_) => Color::PINK,
+            Pickup::Shield(_) => Color::CYAN,
+            Pickup::Weapon(_) => Color::ORANGE,
+            Pickup::ScoreMultiplier { .. } => Color::GOLD,
+        }
+    }
+}
+
+/// Multiplies score rewards until the timer runs out.
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component)]
+pub struct ScoreMultiplier {
+    pub factor: f32,
+    #[cfg_attr(feature = "debug", inspectable(ignore))]
+    pub timer: Timer,
+}
+
+pub struct SpawnPickupEvent {
+    pub pickup: Pickup,
+    pub position: Vec2,
+}
+
+/// Applies the effect of a pickup, whether it was collected or obtained some other way.
+pub struct ApplyPickupEvent {
+    pub receiver: Entity,
+    pub pickup: Pickup,
+}
+
+/// Systems handling the events sent by applied pickups should run after this label,
+/// so items bought in the shop are ready before gameplay resumes.
+pub const APPLY_PICKUPS_LABEL: &str = "apply_pickups";
+const COLLECT_PICKUPS_LABEL: &str = "collect_pickups";
+
+pub struct PickupPlugin;
+
+impl Plugin for PickupPlugin {
+    fn build(&self, app: &mut App) {
+        #[cfg(feature = "debug")]
+        app.register_inspectable::<ScoreMultiplier>();
+        app.add_tick_event::<SpawnPickupEvent>()
+            .add_tick_event::<ApplyPickupEvent>()
+            .add_system_set_to_stage(
+                FIXED_UPDATE,
+                SystemSet::on_update(GameState::Gameplay)
+                    .with_system(spawn_pickups.after(DESPAWN_DEAD_LABEL))
+                    .with_system(collect_pickups.label(COLLECT_PICKUPS_LABEL))
+                    .with_system(expire_score_multipliers),
+            )
+            .add_system_set_to_stage(
+                FIXED_UPDATE,
+                // Also applies the items bought in the shop.
+                SystemSet::new()
+                    .with_run_criteria(GameState::in_game)
+                    .with_system(
+                        apply_pickups
+                            .label(APPLY_PICKUPS_LABEL)
+                            .after(COLLECT_PICKUPS_LABEL),
+                    ),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Countdown).with_system(despawn_with::<Pickup>),
+            );
+    }
+}
+
+fn spawn_pickups(mut commands: Commands, mut events: EventReader<SpawnPickupEvent>) {
+    for event in events.iter() {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: event.pickup.color(),
+                    custom_size: Some(Vec2::splat(PICKUP_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_translation(event.position.extend(0.0)),
+                ..default()
+            })
+            .insert(event.pickup.clone())
+            .insert(Lifetime {
+                timer: Timer::from_seconds(PICKUP_LIFETIME, false),
+            })
+            .insert(RigidBody::KinematicVelocityBased)
+            .insert(SensorShape)
+            .insert(CollisionShape::Cuboid {
+                half_extends: Vec3::splat(PICKUP_SIZE / 2.0),
+                border_radius: None,
+            })
+            .insert(Velocity::from_linear(Vec3::new(0.0, -PICKUP_SPEED, 0.0)))
+            .insert(CollisionLayers::new(
+                PhysicsLayers::Pickup,
+                PhysicsLayers::Player,
+            ))
+            .insert(Name::new("Pickup"));
+    }
+}
+
+fn collect_pickups(
+    mut commands: Commands,
+    mut collision_events: EventReader<TickCollisionEvent>,
+    mut apply_events: EventWriter<ApplyPickupEvent>,
+    pickups: Query<&Pickup>,
+    players: Query<(), With<Player>>,
+) {
+    // Pickups despawned during this run may be touched by another player as well.
+    let mut collected = HashSet::new();
+    for (data1, data2) in collision_events.iter().filter_map(|e| match &e.0 {
+        CollisionEvent::Started(data1, data2) => Some((data1, data2)),
+        _ => None,
+    }) {
+        let entity1 = data1.rigid_body_entity();
+        let entity2 = data2.rigid_body_entity();
+        let (player, pickup_entity) = if players.get(entity1).is_ok() {
+            (entity1, entity2)
+        } else {
+            (entity2, entity1)
+        };
+
+        if collected.contains(&pickup_entity) {
+            continue;
+        }
+        if let Some((pickup, _)) = pickups
+            .get(pickup_entity)
+            .ok()
+            .zip(players.get(player).ok())
+        {
+            collected.insert(pickup_entity);
+            apply_events.send(ApplyPickupEvent {
+                receiver: player,
+                pickup: pickup.clone(),
+            });
+            commands.entity(pickup_entity).despawn_recursive();
+        }
+    }
+}
+
+fn apply_pickups(
+    mut commands: Commands,
+    mut events: EventReader<ApplyPickupEvent>,
+    mut equip_events: EventWriter<EquipWeaponEvent>,
+    mut upgrade_events: EventWriter<UpgradeWeaponEvent>,
+    asset_server: Res<AssetServer>,
+    mut weapon_prefabs: ResMut<Assets<WeaponPrefab>>,
+    mut receivers: Query<(
+        &mut Health,
+        Option<&mut Shield>,
+        Option<&mut WeaponInventory>,
+        Option<&Children>,
+    )>,
+    slots: Query<
+        (
+            Option<&Weapon>,
+            Option<&EquippedWeapon>,
+            Option<&WeaponLevel>,
+        ),
+        With<WeaponSlot>,
+    >,
+) {
+    for ApplyPickupEvent { receiver, pickup } in events.iter() {
+        let (mut health, shield, inventory, children) = match receivers.get_mut(*receiver) {
+            Ok(receiver) => receiver,
+            Err(_) => continue,
+        };
+
+        match pickup {
+            Pickup::Repair(amount) => {
+                health.current = (health.current + amount).min(health.max);
+            }
+            Pickup::MaxHealth(amount) => {
+                health.max += amount;
+                health.current += amount;
+            }
+            Pickup::Shield(amount) => match shield {
+                Some(mut shield) => shield.current = (shield.current + amount).min(shield.max),
+                None => {
+                    commands.entity(*receiver).insert(Shield {
+                        current: *amount,
+                        max: *amount,
+                        regen_rate: 0.0,
+                        regen_progress: 0.0,
+                        regen_delay: Timer::from_seconds(0.0, false),
+                    });
+                }
+            },
+            Pickup::Weapon(weapon) => {
+                let handle = match weapon {
+                    PrefabHandle::Prefab(weapon) => weapon_prefabs.add(weapon.clone()),
+                    PrefabHandle::Asset(path) => asset_server.get_handle(path.as_str()),
+                };
+                let mut slot_entities = children.iter().flat_map(|children| children.iter());
+                let name = weapon_prefabs
+                    .get(&handle)
+                    .and_then(|weapon| weapon.name.as_ref());
+                let upgradable_slot =
+                    slot_entities
+                        .clone()
+                        .find(|&&child| match slots.get(child) {
+                            Ok((_, Some(EquippedWeapon(equipped)), Some(level))) => {
+                                name.is_some()
+                                    && equipped.name.as_ref() == name
+                                    && level.current < level.max
+                            }
+                            _ => false,
+                        });
+                if let Some(&slot_entity) = upgradable_slot {
+                    upgrade_events.send(UpgradeWeaponEvent { slot_entity });
+                    continue;
+                }
+                let free_slot =
+                    slot_entities.find(|&&child| matches!(slots.get(child), Ok((None, _, _))));
+                match free_slot.zip(weapon_prefabs.get(&handle)) {
+                    Some((&slot_entity, weapon)) => equip_events.send(EquipWeaponEvent {
+                        slot_entity,
+                        weapon: weapon.clone(),
+                    }),
+                    None => {
+                        if let Some(mut inventory) = inventory {
+                            inventory.weapons.push(handle);
+                        }
+                    }
+                }
+            }
+            Pickup::ScoreMultiplier { factor, duration } => {
+                commands.entity(*receiver).insert(ScoreMultiplier {
+                    factor: *factor,
+                    timer: Timer::from_seconds(*duration, false),
+                });
+            }
+        }
+    }
+}
+
+fn expire_score_multipliers(
+    mut commands: Commands,
+    timestep: Res<Timestep>,
+    mut multipliers: Query<(Entity, &mut ScoreMultiplier)>,
+) {
+    for (entity, mut multiplier) in multipliers.iter_mut() {
+        if multiplier.timer.tick(timestep.delta()).just_finished() {
+            commands.entity(entity).remove::<ScoreMultiplier>();
+        }
+    }
+}
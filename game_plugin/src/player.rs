@@ -6,77 +6,239 @@ use bevy_inspector_egui::{Inspectable, RegisterInspectable};
 use heron::prelude::*;
 
 use crate::{
-    combat::{Health, Scores, ShootEvent, UnitPrefab},
+    actions::{Action, TickActions},
+    combat::{
+        Engine, Health, Invulnerable, Lives, Scores, ShootEvent, SourceUnit, UnitPrefab,
+        DESPAWN_DEAD_LABEL, SHOOT_LABEL,
+    },
+    replay::{REPLAY_INPUT_LABEL, REWIND_REPLAY_LABEL},
     states::GameState,
+    timestep::{Timestep, FIXED_UPDATE},
+    wave::LevelCompletedEvent,
     PhysicsLayers,
 };
 
+const DEFAULT_SHIP_PATH: &str = "units/player.unit.ron";
+const SPAWN_POSITION: Vec3 = Vec3::new(0.0, -150.0, 0.0);
+/// Horizontal distance between the spawn points of two players.
+const SPAWN_SPACING: f32 = 120.0;
+pub const MAX_PLAYERS: usize = 2;
+
 #[cfg_attr(feature = "debug", derive(Inspectable))]
 #[derive(Component)]
-pub struct Player {
-    pub speed: f32,
+pub struct Player;
+
+/// Starts from 0, picks the controls, spawn point and HUD entry of the player.
+#[cfg_attr(feature = "debug", derive(Inspectable))]
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerIndex(pub usize);
+
+/// Number of players spawned when the game starts.
+pub struct PlayerCount(pub usize);
+
+impl Default for PlayerCount {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+/// Scores of the players out of lives, the game is over once every player is
+/// or once the level is completed.
+#[derive(Default)]
+struct EliminatedScores(u32);
+
+/// Path of the unit asset spawned as the players.
+pub struct SelectedShip(pub String);
+
+impl Default for SelectedShip {
+    fn default() -> Self {
+        Self(DEFAULT_SHIP_PATH.into())
+    }
 }
 
+/// Lost a life and comes back at the spawn point once the timer runs out.
+#[derive(Component)]
+pub struct Respawning {
+    pub timer: Timer,
+}
+
+/// Sent once every player is out of lives or the level is completed.
 pub struct GameOverEvent {
     pub score: u32,
 }
 
+const COMPLETE_LEVEL_LABEL: &str = "complete_level";
+
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         #[cfg(feature = "debug")]
-        app.register_inspectable::<Player>();
-        app.add_event::<GameOverEvent>()
-            .add_system_set(SystemSet::on_enter(GameState::Countdown).with_system(spawn_player))
+        app.register_inspectable::<Player>()
+            .register_inspectable::<PlayerIndex>();
+        app.init_resource::<SelectedShip>()
+            .init_resource::<PlayerCount>()
+            .init_resource::<EliminatedScores>()
+            .add_event::<GameOverEvent>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Countdown)
+                    .with_system(spawn_players.after(REWIND_REPLAY_LABEL)),
+            )
+            .add_system_set_to_stage(
+                FIXED_UPDATE,
+                SystemSet::on_update(GameState::Gameplay)
+                    .with_system(player_movement.after(REPLAY_INPUT_LABEL))
+                    .with_system(player_shoot.label(SHOOT_LABEL).after(REPLAY_INPUT_LABEL))
+                    .with_system(respawn_players)
+                    .with_system(track_player_dead.after(DESPAWN_DEAD_LABEL)),
+            )
             .add_system_set(
                 SystemSet::on_update(GameState::Gameplay)
-                    .with_system(player_movement)
-                    .with_system(player_shoot)
-                    .with_system(track_player_dead)
-                    .with_system(handle_game_over),
+                    .with_system(complete_level.label(COMPLETE_LEVEL_LABEL))
+                    .with_system(handle_game_over.after(COMPLETE_LEVEL_LABEL)),
             );
     }
 }
 
-fn spawn_player(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let player_size = Vec2::splat(32.0);
-    let prefab_handle: Handle<UnitPrefab> = asset_server.get_handle("units/player.unit.ron");
-    commands
-        .spawn()
-        .insert(RigidBody::Dynamic)
-        .insert(CollisionShape::Cuboid {
-            half_extends: player_size.extend(0.0) / 2.0,
-            border_radius: None,
-        })
-        .insert(Velocity::default())
-        .insert(RotationConstraints::lock())
-        .insert(PhysicMaterial {
-            friction: 0.0,
-            ..default()
-        })
-        .insert(Player { speed: 200.0 })
-        .insert(Scores::default())
-        .insert(prefab_handle)
-        .insert_bundle(TransformBundle::from_transform(Transform::from_xyz(
-            0.0, -150.0, 0.0,
-        )))
-        .insert(CollisionLayers::new(
-            PhysicsLayers::Player,
-            PhysicsLayers::Enemy,
-        ));
+fn spawn_players(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    selected_ship: Res<SelectedShip>,
+    player_count: Res<PlayerCount>,
+    mut eliminated_scores: ResMut<EliminatedScores>,
+) {
+    eliminated_scores.0 = 0;
+    let prefab_handle: Handle<UnitPrefab> = asset_server.get_handle(selected_ship.0.as_str());
+    for index in 0..player_count.0 {
+        commands
+            .spawn()
+            .insert(RigidBody::Dynamic)
+            .insert(Velocity::default())
+            .insert(RotationConstraints::lock())
+            .insert(PhysicMaterial {
+                friction: 0.0,
+                ..default()
+            })
+            .insert(Player)
+            .insert(PlayerIndex(index))
+            .insert(Scores::default())
+            .insert(SourceUnit(prefab_handle.clone()))
+            .insert(prefab_handle.clone())
+            .insert_bundle(TransformBundle::from_transform(
+                Transform::from_translation(spawn_position(index, player_count.0)),
+            ))
+            .insert(player_layers());
+    }
+}
+
+/// Players are spread evenly around the middle spawn point.
+fn spawn_position(index: usize, count: usize) -> Vec3 {
+    let offset = index as f32 - (count as f32 - 1.0) / 2.0;
+    SPAWN_POSITION + offset * SPAWN_SPACING * Vec3::X
+}
+
+fn player_layers() -> CollisionLayers {
+    CollisionLayers::none()
+        .with_group(PhysicsLayers::Player)
+        .with_masks([PhysicsLayers::Enemy, PhysicsLayers::Pickup])
+}
+
+fn is_eliminated(health: &Health, lives: Option<&Lives>) -> bool {
+    health.is_dead() && lives.map_or(true, |lives| lives.remaining == 0)
 }
 
 pub fn track_player_dead(
+    mut commands: Commands,
     mut game_over_events: EventWriter<GameOverEvent>,
-    players: Query<(&Health, Option<&Scores>), (With<Player>, Changed<Health>)>,
+    mut eliminated_scores: ResMut<EliminatedScores>,
+    dead_players: Query<
+        (Entity, &Health, Option<&Lives>, Option<&Scores>),
+        (With<Player>, Changed<Health>),
+    >,
+    players: Query<(&Health, Option<&Lives>), With<Player>>,
 ) {
-    if let Ok((health, scores)) = players.get_single() {
-        if health.is_dead() {
-            game_over_events.send(GameOverEvent {
-                score: scores.map(|scores| scores.amount).unwrap_or(0),
-            });
+    let mut eliminated = false;
+    for (entity, health, lives, scores) in dead_players.iter() {
+        if !health.is_dead() {
+            continue;
+        }
+        match lives {
+            Some(lives) if lives.remaining > 0 => {
+                // Out of the game until respawned, not even colliding.
+                commands
+                    .entity(entity)
+                    .remove::<Invulnerable>()
+                    .insert(Respawning {
+                        timer: Timer::from_seconds(lives.respawn_delay, false),
+                    })
+                    .insert(CollisionLayers::none())
+                    .insert(Velocity::default())
+                    .insert(Visibility { is_visible: false });
+            }
+            _ => {
+                eliminated_scores.0 += scores.map(|scores| scores.amount).unwrap_or(0);
+                eliminated = true;
+            }
+        }
+    }
+
+    if eliminated
+        && players
+            .iter()
+            .all(|(health, lives)| is_eliminated(health, lives))
+    {
+        game_over_events.send(GameOverEvent {
+            score: eliminated_scores.0,
+        });
+    }
+}
+
+fn respawn_players(
+    mut commands: Commands,
+    timestep: Res<Timestep>,
+    player_count: Res<PlayerCount>,
+    mut players: Query<
+        (
+            Entity,
+            &PlayerIndex,
+            &mut Respawning,
+            &mut Health,
+            &mut Transform,
+            Option<&Lives>,
+        ),
+        With<Player>,
+    >,
+) {
+    for (entity, index, mut respawning, mut health, mut transform, lives) in players.iter_mut() {
+        if !respawning.timer.tick(timestep.delta()).just_finished() {
+            continue;
         }
+        health.current = health.max;
+        health.last_hit_by = None;
+        *transform = Transform::from_translation(spawn_position(index.0, player_count.0));
+        commands
+            .entity(entity)
+            .remove::<Respawning>()
+            .insert(player_layers())
+            .insert(Visibility::default())
+            .insert(Invulnerable {
+                timer: Timer::from_seconds(lives.map_or(0.0, |lives| lives.invulnerability), false),
+            });
+    }
+}
+
+/// Ends the game once the last wave is cleared, with the scores of every player.
+fn complete_level(
+    mut events: EventReader<LevelCompletedEvent>,
+    mut game_over_events: EventWriter<GameOverEvent>,
+    eliminated_scores: Res<EliminatedScores>,
+    players: Query<&Scores, With<Player>>,
+) {
+    if events.iter().next().is_some() {
+        let scores = players.iter().map(|scores| scores.amount).sum::<u32>();
+        game_over_events.send(GameOverEvent {
+            score: eliminated_scores.0 + scores,
+        });
     }
 }
 
@@ -85,38 +247,49 @@ pub fn handle_game_over(
     mut state: ResMut<State<GameState>>,
 ) {
     if events.iter().next().is_some() {
+        // Takes over a shop or pause queued by the same frame.
         state
-            .set(GameState::GameOver)
+            .overwrite_set(GameState::GameOver)
             .expect("Unable to set state to GameOver");
     }
 }
 
 pub fn player_movement(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut players: Query<(&Player, &mut Velocity)>,
+    timestep: Res<Timestep>,
+    tick_actions: Res<TickActions>,
+    mut players: Query<(&PlayerIndex, &Engine, &mut Velocity), (With<Player>, Without<Respawning>)>,
 ) {
-    for (player, mut velocity) in players.iter_mut() {
-        let up = keyboard_input.pressed(KeyCode::W) || keyboard_input.pressed(KeyCode::Up);
-        let down = keyboard_input.pressed(KeyCode::S) || keyboard_input.pressed(KeyCode::Down);
-        let left = keyboard_input.pressed(KeyCode::A) || keyboard_input.pressed(KeyCode::Left);
-        let right = keyboard_input.pressed(KeyCode::D) || keyboard_input.pressed(KeyCode::Right);
+    for (index, engine, mut velocity) in players.iter_mut() {
+        let actions = match tick_actions.player(index.0) {
+            Some(actions) => actions,
+            None => continue,
+        };
+        let up = actions.pressed(Action::MoveUp);
+        let down = actions.pressed(Action::MoveDown);
+        let left = actions.pressed(Action::MoveLeft);
+        let right = actions.pressed(Action::MoveRight);
 
         let x_axis = -(left as i8) + right as i8;
         let y_axis = -(down as i8) + up as i8;
 
         let move_delta = Vec3::new(x_axis as f32, y_axis as f32, 0.0).normalize_or_zero();
 
-        velocity.linear = move_delta * player.speed;
+        let change = move_delta * engine.speed - velocity.linear;
+        let max_change = engine.acceleration * timestep.delta_seconds();
+        velocity.linear += change.clamp_length_max(max_change);
     }
 }
 
 pub fn player_shoot(
     mut shoot_events: EventWriter<ShootEvent>,
-    keyboard_input: Res<Input<KeyCode>>,
-    players: Query<Entity, With<Player>>,
+    tick_actions: Res<TickActions>,
+    players: Query<(Entity, &PlayerIndex), (With<Player>, Without<Respawning>)>,
 ) {
-    if keyboard_input.pressed(KeyCode::Space) {
-        for shooter in players.iter() {
+    for (shooter, index) in players.iter() {
+        let fire = tick_actions
+            .player(index.0)
+            .map_or(false, |actions| actions.pressed(Action::Fire));
+        if fire {
             shoot_events.send(ShootEvent { shooter })
         }
     }
@@ -0,0 +1,374 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::{TickActions, READ_ACTIONS_LABEL},
+    player::{PlayerCount, SelectedShip},
+    rng::GameRng,
+    shop::{CloseShopEvent, PurchaseEvent},
+    states::GameState,
+    timestep::{Timestep, FIXED_UPDATE},
+};
+
+/// Gameplay systems reading [`TickActions`], and shop systems reading purchases,
+/// should run after this label, so they see the replayed ones instead of the live input.
+pub const REPLAY_INPUT_LABEL: &str = "replay_input";
+/// Systems spawning the game on entering `GameState::Countdown` should run after this label,
+/// so they see the settings of the replay being played back.
+pub const REWIND_REPLAY_LABEL: &str = "rewind_replay";
+
+/// Actions of every gameplay tick, played back with the seed and tick rate they were recorded with.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Replay {
+    pub seed: u64,
+    pub tick_rate: f64,
+    /// Path of the player's unit asset, the default ship is used when missing.
+    #[serde(default)]
+    pub ship: Option<String>,
+    #[serde(default = "default_players")]
+    pub players: usize,
+    pub frames: Vec<TickActions>,
+    #[serde(default)]
+    pub purchases: Vec<ReplayPurchase>,
+}
+
+/// Purchase made in the shop opened after `tick` gameplay ticks.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ReplayPurchase {
+    pub tick: usize,
+    pub purchase: PurchaseEvent,
+}
+
+fn default_players() -> usize {
+    1
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let bytes = fs::read(path)?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let replay = ron::ser::to_string_pretty(self, Default::default())?;
+        fs::write(path, replay)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub enum ReplayMode {
+    Record(PathBuf),
+    Playback(PathBuf),
+}
+
+struct ReplayRecorder {
+    path: PathBuf,
+    frames: Vec<TickActions>,
+    purchases: Vec<ReplayPurchase>,
+}
+
+struct ReplayPlayer {
+    replay: Replay,
+    cursor: usize,
+    /// Index of the next purchase to make.
+    purchase_cursor: usize,
+}
+
+pub enum ReplayPlugin {
+    Disabled,
+    Record(PathBuf),
+    Playback(Replay),
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        match self {
+            ReplayPlugin::Disabled => {}
+            ReplayPlugin::Record(path) => {
+                app.insert_resource(ReplayRecorder {
+                    path: path.clone(),
+                    frames: Vec::new(),
+                    purchases: Vec::new(),
+                });
+            }
+            ReplayPlugin::Playback(replay) => {
+                app.insert_resource(ReplayPlayer {
+                    replay: replay.clone(),
+                    cursor: 0,
+                    purchase_cursor: 0,
+                });
+            }
+        }
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Countdown)
+                .with_system(rewind_replay.label(REWIND_REPLAY_LABEL)),
+        )
+        .add_system_set_to_stage(
+            FIXED_UPDATE,
+            SystemSet::on_update(GameState::Gameplay).with_system(
+                replay_input
+                    .label(REPLAY_INPUT_LABEL)
+                    .after(READ_ACTIONS_LABEL),
+            ),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Shop)
+                .with_system(record_purchases)
+                .with_system(replay_purchases.label(REPLAY_INPUT_LABEL)),
+        )
+        .add_system_set(SystemSet::on_exit(GameState::Gameplay).with_system(finish_replay));
+    }
+}
+
+fn rewind_replay(
+    recorder: Option<ResMut<ReplayRecorder>>,
+    player: Option<ResMut<ReplayPlayer>>,
+    mut selected_ship: ResMut<SelectedShip>,
+    mut player_count: ResMut<PlayerCount>,
+) {
+    if let Some(mut recorder) = recorder {
+        recorder.frames.clear();
+        recorder.purchases.clear();
+    }
+    if let Some(mut player) = player {
+        player.cursor = 0;
+        player.purchase_cursor = 0;
+        *selected_ship = player
+            .replay
+            .ship
+            .clone()
+            .map_or_else(SelectedShip::default, SelectedShip);
+        player_count.0 = player.replay.players;
+    }
+}
+
+fn replay_input(
+    mut actions: ResMut<TickActions>,
+    recorder: Option<ResMut<ReplayRecorder>>,
+    player: Option<ResMut<ReplayPlayer>>,
+) {
+    if let Some(mut recorder) = recorder {
+        recorder.frames.push(actions.clone());
+    }
+    if let Some(mut player) = player {
+        let frame = player
+            .replay
+            .frames
+            .get(player.cursor)
+            .cloned()
+            .unwrap_or_default();
+        if player.cursor == player.replay.frames.len() {
+            info!("Replay finished");
+        }
+        player.cursor += 1;
+        *actions = frame;
+    }
+}
+
+fn record_purchases(
+    recorder: Option<ResMut<ReplayRecorder>>,
+    mut events: EventReader<PurchaseEvent>,
+) {
+    if let Some(mut recorder) = recorder {
+        let tick = recorder.frames.len();
+        for purchase in events.iter() {
+            recorder.purchases.push(ReplayPurchase {
+                tick,
+                purchase: purchase.clone(),
+            });
+        }
+    }
+}
+
+/// Makes the recorded purchases of this visit and leaves the shop right away.
+fn replay_purchases(
+    player: Option<ResMut<ReplayPlayer>>,
+    mut purchase_events: EventWriter<PurchaseEvent>,
+    mut close_events: EventWriter<CloseShopEvent>,
+) {
+    if let Some(mut player) = player {
+        // Purchases of a visit that was missed are made on the next one.
+        while let Some(recorded) = player.replay.purchases.get(player.purchase_cursor) {
+            if recorded.tick > player.cursor {
+                break;
+            }
+            purchase_events.send(recorded.purchase.clone());
+            player.purchase_cursor += 1;
+        }
+        close_events.send(CloseShopEvent);
+    }
+}
+
+fn finish_replay(
+    recorder: Option<Res<ReplayRecorder>>,
+    rng: Res<GameRng>,
+    timestep: Res<Timestep>,
+    selected_ship: Res<SelectedShip>,
+    player_count: Res<PlayerCount>,
+) {
+    if let Some(recorder) = recorder {
+        let replay = Replay {
+            seed: rng.seed(),
+            tick_rate: timestep.tick_rate(),
+            ship: Some(selected_ship.0.clone()),
+            players: player_count.0,
+            frames: recorder.frames.clone(),
+            purchases: recorder.purchases.clone(),
+        };
+        match replay.save(&recorder.path) {
+            Ok(()) => info!("Replay saved to {}", recorder.path.display()),
+            Err(err) => error!("Unable to save replay: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env, thread,
+        time::{Duration, Instant},
+    };
+
+    use bevy::{
+        asset::AssetServerSettings,
+        input::{keyboard::KeyboardInput, ElementState},
+    };
+
+    use super::*;
+    use crate::{combat::Scores, player::Player, GamePlugin};
+
+    const GAMEPLAY_TICKS: usize = 1200;
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// Ends the game from `CoreStage::Update`, where transitions are handled.
+    #[derive(Default)]
+    struct EndGame(bool);
+
+    fn end_game(mut end: ResMut<EndGame>, mut state: ResMut<State<GameState>>) {
+        if end.0 {
+            end.0 = false;
+            state
+                .set(GameState::GameOver)
+                .expect("Unable to change state to GameOver");
+        }
+    }
+
+    fn app(mode: ReplayMode) -> App {
+        let mut app = App::new();
+        app.insert_resource(AssetServerSettings {
+            asset_folder: "../assets".into(),
+            ..default()
+        })
+        .add_plugin(GamePlugin::headless().with_seed(7).with_replay(mode))
+        .init_resource::<EndGame>()
+        .add_system(end_game);
+        app
+    }
+
+    fn current_state(app: &App) -> GameState {
+        app.world.resource::<State<GameState>>().current().clone()
+    }
+
+    fn update_until(app: &mut App, state: GameState) {
+        let start = Instant::now();
+        while current_state(app) != state {
+            assert!(
+                start.elapsed() < TIMEOUT,
+                "Stuck in {:?}",
+                current_state(app)
+            );
+            app.update();
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn send_key(app: &mut App, key: KeyCode, state: ElementState) {
+        app.world
+            .resource_mut::<Events<KeyboardInput>>()
+            .send(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key),
+                state,
+            });
+    }
+
+    /// Starts a game from the main menu.
+    fn start_game(app: &mut App) {
+        update_until(app, GameState::MainMenu);
+        // Through the main menu then the ship selection.
+        for _ in 0..2 {
+            send_key(app, KeyCode::Return, ElementState::Pressed);
+            app.update();
+            send_key(app, KeyCode::Return, ElementState::Released);
+            app.update();
+        }
+        update_until(app, GameState::Gameplay);
+    }
+
+    /// Returns the score of the players, the game is over afterwards.
+    fn end_game_score(app: &mut App) -> u32 {
+        let score = app
+            .world
+            .query_filtered::<&Scores, With<Player>>()
+            .iter(&app.world)
+            .map(|scores| scores.amount)
+            .sum();
+
+        app.world.resource_mut::<EndGame>().0 = true;
+        update_until(app, GameState::GameOver);
+        score
+    }
+
+    /// Plays a game for `GAMEPLAY_TICKS`, one per update, firing if asked.
+    fn play(app: &mut App, fire: bool) -> u32 {
+        start_game(app);
+        if fire {
+            send_key(app, KeyCode::Space, ElementState::Pressed);
+        }
+        for _ in 0..GAMEPLAY_TICKS {
+            app.update();
+        }
+        end_game_score(app)
+    }
+
+    /// Plays a replay back for `GAMEPLAY_TICKS` with ticks following the time, as with a window.
+    /// Frames take uneven times, so some run no tick and others run a late one.
+    fn play_back_in_real_time(app: &mut App) -> u32 {
+        app.world.resource_mut::<Timestep>().tick_per_update = false;
+        start_game(app);
+        let frame_times = [0, 4, 10, 25].map(Duration::from_millis);
+        for frame_time in frame_times.iter().cycle() {
+            if app.world.resource::<ReplayPlayer>().cursor == GAMEPLAY_TICKS {
+                break;
+            }
+            assert_ne!(current_state(app), GameState::GameOver);
+            thread::sleep(*frame_time);
+            app.update();
+        }
+        end_game_score(app)
+    }
+
+    #[test]
+    fn playback_reaches_recorded_score() {
+        let path = env::temp_dir().join("space_battle_round_trip.replay.ron");
+
+        let recorded_score = play(&mut app(ReplayMode::Record(path.clone())), true);
+        let replay = Replay::load(&path).expect("Unable to load recorded replay");
+        // The tick ending the game is recorded too.
+        assert_eq!(replay.frames.len(), GAMEPLAY_TICKS + 1);
+
+        let replayed_score = play(&mut app(ReplayMode::Playback(path.clone())), false);
+        let real_time_score = play_back_in_real_time(&mut app(ReplayMode::Playback(path.clone())));
+        fs::remove_file(&path).ok();
+        // Enemies were destroyed, so the replay has more to match than an empty score.
+        assert!(recorded_score > 0);
+        assert_eq!(recorded_score, replayed_score);
+        assert_eq!(recorded_score, real_time_score);
+    }
+}
@@ -0,0 +1,243 @@
+#![allow(clippy::type_complexity)]
+
+use bevy::prelude::*;
+
+use crate::{
+    actions::{Action, Bindings},
+    combat::UnitPrefab,
+    despawn_with,
+    loading::{AssetsFolder, FontAssets},
+    main_menu::{StartGameEvent, NORMAL_BUTTON},
+    player::SelectedShip,
+    states::GameState,
+};
+
+const PREVIEW_SIZE: f32 = 64.0;
+
+#[derive(Component, Clone)]
+enum ShipSelectButton {
+    Ship(String),
+    Back,
+}
+
+#[derive(Component)]
+struct ShipSelectUITag;
+
+/// Lists the playable units, the chosen one is spawned for every player.
+pub struct ShipSelectPlugin;
+
+impl Plugin for ShipSelectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(GameState::ShipSelect).with_system(setup_ui))
+            .add_system_set(
+                SystemSet::on_update(GameState::ShipSelect)
+                    .with_system(handle_button_click)
+                    .with_system(handle_keyboard),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::ShipSelect)
+                    .with_system(despawn_with::<ShipSelectUITag>),
+            );
+    }
+}
+
+fn setup_ui(
+    mut commands: Commands,
+    fonts: Res<FontAssets>,
+    folder: Res<AssetsFolder>,
+    asset_server: Res<AssetServer>,
+    units: Res<Assets<UnitPrefab>>,
+) {
+    let ships = folder
+        .units
+        .iter()
+        .filter_map(|handle| {
+            let unit = units.get(handle).filter(|unit| unit.playable)?;
+            let path = asset_server.get_handle_path(handle)?;
+            Some((path.path().to_string_lossy().into_owned(), unit))
+        })
+        .collect::<Vec<_>>();
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(ShipSelectUITag)
+        .insert(Name::new("Ship Select UI"))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "SELECT SHIP",
+                    TextStyle {
+                        font: fonts.font.clone(),
+                        font_size: 56.0,
+                        color: Color::ORANGE,
+                    },
+                    Default::default(),
+                ),
+                ..default()
+            });
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        margin: Rect::all(Val::Px(20.0)),
+                        ..default()
+                    },
+                    color: Color::NONE.into(),
+                    ..default()
+                })
+                .with_children(|row| {
+                    for (path, unit) in ships {
+                        spawn_ship_card(row, path, unit, &asset_server, fonts.font.clone());
+                    }
+                });
+            spawn_back_button(parent, fonts.font.clone());
+        });
+}
+
+fn spawn_ship_card(
+    parent: &mut ChildBuilder,
+    path: String,
+    unit: &UnitPrefab,
+    asset_server: &AssetServer,
+    font: Handle<Font>,
+) {
+    let text_style = TextStyle {
+        font,
+        font_size: 20.0,
+        color: Color::BLACK,
+    };
+    let stats = format!(
+        "Health: {}\nSlots: {}\nSpeed: {}",
+        unit.health,
+        unit.weapon_slots.len(),
+        unit.engine.speed
+    );
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(180.0), Val::Px(220.0)),
+                margin: Rect::all(Val::Px(10.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: NORMAL_BUTTON.into(),
+            ..default()
+        })
+        .with_children(|card| {
+            card.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    unit.name.clone(),
+                    TextStyle {
+                        font_size: 28.0,
+                        ..text_style.clone()
+                    },
+                    Default::default(),
+                ),
+                ..default()
+            });
+            card.spawn_bundle(ImageBundle {
+                style: Style {
+                    size: Size::new(Val::Px(PREVIEW_SIZE), Val::Px(PREVIEW_SIZE)),
+                    margin: Rect::all(Val::Px(10.0)),
+                    ..default()
+                },
+                image: asset_server.load(&unit.body).into(),
+                ..default()
+            });
+            card.spawn_bundle(TextBundle {
+                text: Text::with_section(stats, text_style, Default::default()),
+                ..default()
+            });
+        })
+        .insert(ShipSelectButton::Ship(path));
+}
+
+fn spawn_back_button(parent: &mut ChildBuilder, font: Handle<Font>) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(150.0), Val::Px(50.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: NORMAL_BUTTON.into(),
+            ..default()
+        })
+        .with_children(|button| {
+            button.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "Back",
+                    TextStyle {
+                        font,
+                        font_size: 32.0,
+                        color: Color::BLACK,
+                    },
+                    Default::default(),
+                ),
+                ..default()
+            });
+        })
+        .insert(ShipSelectButton::Back);
+}
+
+fn handle_button_click(
+    interaction_query: Query<
+        (&Interaction, &ShipSelectButton),
+        (Changed<Interaction>, With<Button>),
+    >,
+    mut selected_ship: ResMut<SelectedShip>,
+    mut start_game_events: EventWriter<StartGameEvent>,
+    mut state: ResMut<State<GameState>>,
+) {
+    for (interaction, tag) in interaction_query.iter() {
+        if let Interaction::Clicked = *interaction {
+            match tag {
+                ShipSelectButton::Ship(path) => {
+                    selected_ship.0 = path.clone();
+                    start_game_events.send(StartGameEvent);
+                }
+                ShipSelectButton::Back => {
+                    // Another transition may already be queued by this frame.
+                    if let Err(err) = state.set(GameState::MainMenu) {
+                        warn!("Unable to change state to MainMenu: {}", err);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Starts with the previously selected ship.
+fn handle_keyboard(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    bindings: Res<Bindings>,
+    mut start_game_events: EventWriter<StartGameEvent>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let actions = &bindings.common;
+    if actions.just_pressed(Action::Confirm, &keyboard_input) {
+        start_game_events.send(StartGameEvent);
+    }
+
+    if actions.just_pressed(Action::Back, &keyboard_input) {
+        // The main menu exits the game on back.
+        actions.reset(Action::Back, &mut keyboard_input);
+        if let Err(err) = state.set(GameState::MainMenu) {
+            warn!("Unable to change state to MainMenu: {}", err);
+        }
+    }
+}
@@ -0,0 +1,361 @@
+#![allow(clippy::type_complexity)]
+
+use bevy::{prelude::*, reflect::TypeUuid};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::{Action, Bindings},
+    combat::{Scores, WeaponPrefab},
+    despawn_with,
+    loading::{AssetsFolder, FontAssets},
+    main_menu::NORMAL_BUTTON,
+    pause_physics,
+    pickup::{ApplyPickupEvent, Pickup, APPLY_PICKUPS_LABEL},
+    player::{Player, PlayerCount, PlayerIndex},
+    prefab::PrefabHandle,
+    prefab_loader,
+    replay::REPLAY_INPUT_LABEL,
+    resume_physics,
+    states::GameState,
+    timestep::FIXED_UPDATE,
+    wave::{Level, WaveClearedEvent},
+};
+
+pub struct ShopLoader;
+prefab_loader!(ShopLoader, ShopCatalog, ["shop.ron"]);
+
+/// Items sold besides the weapons, which are priced in their own `.weapon.ron`.
+#[derive(Serialize, Deserialize, TypeUuid, Clone)]
+#[uuid = "b3a8f2d1-6c4e-4f0a-9e57-2d9c1a7b8e34"]
+#[serde(rename = "Shop")]
+pub struct ShopCatalog {
+    pub items: Vec<ShopItem>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename = "Item")]
+pub struct ShopItem {
+    pub name: String,
+    pub price: u32,
+    pub pickup: Pickup,
+}
+
+/// Items offered during the current visit of the shop.
+#[derive(Default)]
+struct ShopOffers(Vec<ShopItem>);
+
+/// Index of the player paying for purchases.
+#[derive(Default)]
+struct ShopBuyer(usize);
+
+#[derive(Component, Clone, Copy)]
+enum ShopButton {
+    Buy(usize),
+    SwitchBuyer,
+    Continue,
+}
+
+#[derive(Component)]
+struct ShopUITag;
+
+/// Sent by every way of leaving the shop, so it is closed only once per frame.
+pub struct CloseShopEvent;
+
+/// Buys an item for a player, if they have the score to pay for it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PurchaseEvent {
+    /// Index of the paying player.
+    pub buyer: usize,
+    /// Name of the item, offers are not always listed in the same order.
+    pub item: String,
+}
+
+/// Purchases waiting for the next tick to be applied, the shop stays open until they are.
+#[derive(Default)]
+struct PendingPurchases(Vec<PurchaseEvent>);
+
+const QUEUE_PURCHASES_LABEL: &str = "queue_purchases";
+
+#[derive(Component)]
+struct ShopScoreText;
+
+/// Opened between waves, once all enemies of a wave are gone.
+/// Purchases are made with the mouse, replays record them with their buyer
+/// and play them back instead of showing the shop.
+pub struct ShopPlugin;
+
+impl Plugin for ShopPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ShopCatalog>()
+            .add_asset_loader(ShopLoader)
+            .init_resource::<ShopOffers>()
+            .init_resource::<ShopBuyer>()
+            .init_resource::<PendingPurchases>()
+            .add_event::<CloseShopEvent>()
+            .add_event::<PurchaseEvent>()
+            .add_system_set(SystemSet::on_update(GameState::Gameplay).with_system(open_shop))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Shop)
+                    .with_system(pause_physics)
+                    .with_system(setup_shop),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Shop)
+                    .with_system(handle_button_click)
+                    .with_system(handle_keyboard)
+                    .with_system(
+                        queue_purchases
+                            .label(QUEUE_PURCHASES_LABEL)
+                            .after(REPLAY_INPUT_LABEL),
+                    )
+                    // Purchases sent along with closing the shop are still applied.
+                    .with_system(close_shop.after(QUEUE_PURCHASES_LABEL))
+                    .with_system(display_scores),
+            )
+            .add_system_set_to_stage(
+                FIXED_UPDATE,
+                SystemSet::on_update(GameState::Shop)
+                    .with_system(apply_purchases.before(APPLY_PICKUPS_LABEL)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Shop)
+                    .with_system(resume_physics)
+                    .with_system(despawn_with::<ShopUITag>),
+            );
+    }
+}
+
+fn open_shop(
+    mut events: EventReader<WaveClearedEvent>,
+    levels: Query<&Level>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if events.iter().next().is_some() && levels.iter().any(|level| !level.completed) {
+        // Another transition, like the game being over, may already be queued this frame.
+        if let Err(err) = state.push(GameState::Shop) {
+            warn!("Unable to push Shop state: {}", err);
+        }
+    }
+}
+
+fn close_shop(
+    mut events: EventReader<CloseShopEvent>,
+    pending: Res<PendingPurchases>,
+    mut closing: Local<bool>,
+    mut state: ResMut<State<GameState>>,
+) {
+    *closing |= events.iter().next().is_some();
+    // Gameplay resumes with the purchases applied, whether or not a tick ran since they were made.
+    if *closing && pending.0.is_empty() {
+        *closing = false;
+        if let Err(err) = state.pop() {
+            warn!("Unable to pop Shop state: {}", err);
+        }
+    }
+}
+
+fn setup_shop(
+    mut commands: Commands,
+    fonts: Res<FontAssets>,
+    folder: Res<AssetsFolder>,
+    catalogs: Res<Assets<ShopCatalog>>,
+    weapons: Res<Assets<WeaponPrefab>>,
+    players: Query<&PlayerIndex, With<Player>>,
+    mut shop_offers: ResMut<ShopOffers>,
+    mut buyer: ResMut<ShopBuyer>,
+) {
+    let weapon_items = folder.weapons.iter().filter_map(|handle| {
+        let weapon = weapons.get(handle)?;
+        Some(ShopItem {
+            name: weapon.name.clone().unwrap_or_else(|| "Weapon".into()),
+            price: weapon.price?,
+            pickup: Pickup::Weapon(PrefabHandle::Prefab(weapon.clone())),
+        })
+    });
+    let mut offers = weapon_items.collect::<Vec<_>>();
+    offers.sort_by_key(|item| item.price);
+    offers.extend(
+        catalogs
+            .get(&folder.shop)
+            .into_iter()
+            .flat_map(|catalog| catalog.items.iter().cloned()),
+    );
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+            ..default()
+        })
+        .insert(ShopUITag)
+        .insert(Name::new("Shop UI"))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    "SHOP",
+                    TextStyle {
+                        font: fonts.font.clone(),
+                        font_size: 72.0,
+                        color: Color::ORANGE,
+                    },
+                    Default::default(),
+                ),
+                ..default()
+            });
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "Score: 0",
+                        TextStyle {
+                            font: fonts.font.clone(),
+                            font_size: 32.0,
+                            color: Color::WHITE,
+                        },
+                        Default::default(),
+                    ),
+                    style: Style {
+                        margin: Rect::all(Val::Px(10.0)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .insert(ShopScoreText);
+            for (i, item) in offers.iter().enumerate() {
+                let text = format!("{} - {}", item.name, item.price);
+                spawn_button(parent, &text, ShopButton::Buy(i), fonts.font.clone());
+            }
+            // Eliminated players are despawned and can't buy anything.
+            if players.iter().count() > 1 {
+                let font = fonts.font.clone();
+                spawn_button(parent, "Switch player", ShopButton::SwitchBuyer, font);
+            }
+            spawn_button(parent, "Continue", ShopButton::Continue, fonts.font.clone());
+        });
+
+    shop_offers.0 = offers;
+    buyer.0 = players.iter().map(|index| index.0).min().unwrap_or(0);
+}
+
+fn spawn_button(parent: &mut ChildBuilder, text: &str, tag: ShopButton, font: Handle<Font>) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(360.0), Val::Px(40.0)),
+                margin: Rect::all(Val::Px(5.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: NORMAL_BUTTON.into(),
+            ..default()
+        })
+        .with_children(|button| {
+            button.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    text,
+                    TextStyle {
+                        font,
+                        font_size: 26.0,
+                        color: Color::BLACK,
+                    },
+                    Default::default(),
+                ),
+                ..default()
+            });
+        })
+        .insert(tag);
+}
+
+fn handle_button_click(
+    interaction_query: Query<(&Interaction, &ShopButton), (Changed<Interaction>, With<Button>)>,
+    offers: Res<ShopOffers>,
+    mut buyer: ResMut<ShopBuyer>,
+    players: Query<&PlayerIndex, With<Player>>,
+    mut purchase_events: EventWriter<PurchaseEvent>,
+    mut close_events: EventWriter<CloseShopEvent>,
+) {
+    for (interaction, tag) in interaction_query.iter() {
+        if let Interaction::Clicked = *interaction {
+            match *tag {
+                ShopButton::Buy(i) => {
+                    if let Some(item) = offers.0.get(i) {
+                        purchase_events.send(PurchaseEvent {
+                            buyer: buyer.0,
+                            item: item.name.clone(),
+                        });
+                    }
+                }
+                ShopButton::SwitchBuyer => {
+                    let mut indices = players.iter().map(|index| index.0).collect::<Vec<_>>();
+                    indices.sort_unstable();
+                    let next = indices.iter().find(|&&index| index > buyer.0);
+                    if let Some(&next) = next.or_else(|| indices.first()) {
+                        buyer.0 = next;
+                    }
+                }
+                ShopButton::Continue => close_events.send(CloseShopEvent),
+            }
+        }
+    }
+}
+
+fn queue_purchases(mut events: EventReader<PurchaseEvent>, mut pending: ResMut<PendingPurchases>) {
+    pending.0.extend(events.iter().cloned());
+}
+
+fn apply_purchases(
+    mut pending: ResMut<PendingPurchases>,
+    offers: Res<ShopOffers>,
+    mut players: Query<(Entity, &PlayerIndex, &mut Scores), With<Player>>,
+    mut apply_events: EventWriter<ApplyPickupEvent>,
+) {
+    for PurchaseEvent { buyer, item } in pending.0.drain(..) {
+        let item = offers.0.iter().find(|offer| offer.name == item);
+        let player = players.iter_mut().find(|(_, index, _)| index.0 == buyer);
+        if let Some((item, (player, _, mut scores))) = item.zip(player) {
+            if scores.amount >= item.price {
+                scores.amount -= item.price;
+                apply_events.send(ApplyPickupEvent {
+                    receiver: player,
+                    pickup: item.pickup.clone(),
+                });
+            }
+        }
+    }
+}
+
+fn handle_keyboard(
+    keyboard_input: Res<Input<KeyCode>>,
+    bindings: Res<Bindings>,
+    mut close_events: EventWriter<CloseShopEvent>,
+) {
+    if bindings
+        .common
+        .just_pressed(Action::Confirm, &keyboard_input)
+    {
+        close_events.send(CloseShopEvent);
+    }
+}
+
+fn display_scores(
+    mut text: Query<&mut Text, With<ShopScoreText>>,
+    scores: Query<(&PlayerIndex, &Scores), With<Player>>,
+    buyer: Res<ShopBuyer>,
+    player_count: Res<PlayerCount>,
+) {
+    let scores = scores.iter().find(|(index, _)| index.0 == buyer.0);
+    if let Some(((_, scores), mut text)) = scores.zip(text.get_single_mut().ok()) {
+        text.sections[0].value = if player_count.0 > 1 {
+            format!("P{} Score: {}", buyer.0 + 1, scores.amount)
+        } else {
+            format!("Score: {}", scores.amount)
+        };
+    }
+}
@@ -1,8 +1,31 @@
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+
 #[derive(Eq, PartialEq, Clone, Hash, Debug)]
 pub enum GameState {
     Loading,
     MainMenu,
+    /// Rebinding of the input actions, opened from the main menu.
+    Controls,
+    ShipSelect,
     Countdown,
     Gameplay,
+    /// Pushed on top of `Gameplay` between waves.
+    Shop,
+    /// Pushed on top of `Gameplay` by the pause action.
+    Paused,
     GameOver,
 }
+
+impl GameState {
+    /// Run criteria of systems which keep running while a state is pushed on top of `Gameplay`,
+    /// like the equipment bought in the shop.
+    pub fn in_game(state: Res<State<GameState>>) -> ShouldRun {
+        if *state.current() == GameState::Gameplay
+            || state.inactives().contains(&GameState::Gameplay)
+        {
+            ShouldRun::Yes
+        } else {
+            ShouldRun::No
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use bevy::{ecs::schedule::ShouldRun, input::InputSystem, prelude::*};
+
+use crate::states::GameState;
+
+/// Stage running the gameplay simulation at a fixed rate, independent of the frame rate.
+/// At most one tick runs per frame, so the physics step can follow every tick:
+/// the game slows down when frames take longer than a tick instead of skipping ahead.
+/// Systems in it must not change `State<GameState>`, transitions are handled in `CoreStage::Update`.
+pub const FIXED_UPDATE: &str = "fixed_update";
+
+pub const DEFAULT_TICK_RATE: f64 = 60.0;
+
+/// Systems running in [`FIXED_UPDATE`] should use it instead of `Time`.
+pub struct Timestep {
+    tick_rate: f64,
+    pub(crate) tick_per_update: bool,
+}
+
+impl Timestep {
+    pub fn tick_rate(&self) -> f64 {
+        self.tick_rate
+    }
+
+    pub fn delta(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.tick_rate)
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta().as_secs_f32()
+    }
+}
+
+/// Keyboard input of the current tick, read into [`TickActions`](crate::actions::TickActions).
+/// Presses are kept until a tick runs and are only just pressed on that tick,
+/// so frames running no tick or several ticks neither drop nor repeat them.
+#[derive(Default)]
+pub struct TickInput(pub Input<KeyCode>);
+
+/// Whether a tick ran on the current frame.
+#[derive(Default)]
+pub struct Ticked(bool);
+
+pub struct TimestepPlugin {
+    pub tick_rate: f64,
+    /// Runs a single tick per `App::update` instead of keeping up with the time,
+    /// so a game driven from code doesn't depend on how long its updates take.
+    pub tick_per_update: bool,
+}
+
+impl Plugin for TimestepPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Timestep {
+            tick_rate: self.tick_rate,
+            tick_per_update: self.tick_per_update,
+        })
+        .init_resource::<TickInput>()
+        .init_resource::<Ticked>()
+        .add_system_to_stage(CoreStage::PreUpdate, latch_input.after(InputSystem))
+        .add_stage_before(
+            CoreStage::Update,
+            FIXED_UPDATE,
+            SystemStage::parallel().with_run_criteria(run_tick),
+        )
+        .add_system_set_to_stage(FIXED_UPDATE, State::<GameState>::get_driver())
+        .add_system_to_stage(FIXED_UPDATE, end_tick.exclusive_system().at_end());
+    }
+}
+
+/// Registers an event read in [`FIXED_UPDATE`]. Unlike `App::add_event`, its events are kept
+/// until two ticks have passed instead of two frames, so frames without a tick don't drop them.
+pub trait AddTickEvent {
+    fn add_tick_event<T: Resource>(&mut self) -> &mut Self;
+}
+
+impl AddTickEvent for App {
+    fn add_tick_event<T: Resource>(&mut self) -> &mut Self {
+        self.init_resource::<Events<T>>().add_system_to_stage(
+            FIXED_UPDATE,
+            Events::<T>::update_system.exclusive_system().at_end(),
+        )
+    }
+}
+
+/// Run criteria of stages which must step along with the ticks, like the physics.
+pub fn on_tick(ticked: Res<Ticked>) -> ShouldRun {
+    if ticked.0 {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+fn run_tick(
+    time: Res<Time>,
+    timestep: Res<Timestep>,
+    mut ticked: ResMut<Ticked>,
+    mut lag: Local<Duration>,
+) -> ShouldRun {
+    ticked.0 = timestep.tick_per_update || {
+        *lag += time.delta();
+        let delta = timestep.delta();
+        // Catches up with at most one late tick, a long frame doesn't speed up the next ones.
+        let ready = *lag >= delta;
+        if ready {
+            *lag = (*lag - delta).min(delta);
+        }
+        ready
+    };
+    if ticked.0 {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+fn latch_input(input: Res<Input<KeyCode>>, mut tick_input: ResMut<TickInput>) {
+    for &key in input.get_pressed().chain(input.get_just_pressed()) {
+        tick_input.0.press(key);
+    }
+    // Keys pressed and released between two ticks are still seen as pressed by the next one.
+    let released = tick_input
+        .0
+        .get_pressed()
+        .filter(|&&key| !input.pressed(key) && !tick_input.0.just_pressed(key))
+        .copied()
+        .collect::<Vec<_>>();
+    for key in released {
+        tick_input.0.release(key);
+    }
+}
+
+fn end_tick(mut tick_input: ResMut<TickInput>) {
+    tick_input.0.clear();
+}
@@ -0,0 +1,237 @@
+use bevy::{asset::LoadState, core::Stopwatch, prelude::*, reflect::TypeUuid};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    despawn_with,
+    enemy::{Enemy, Movement, SpawnEnemyEvent, SPAWN_ENEMY_LABEL},
+    prefab::{Prefab, PrefabHandle, RegisterPrefab},
+    prefab_loader,
+    states::GameState,
+    timestep::{Timestep, FIXED_UPDATE},
+};
+
+pub struct LevelLoader;
+prefab_loader!(LevelLoader, LevelPrefab, ["level.ron"]);
+
+pub struct WaveLoader;
+prefab_loader!(WaveLoader, WavePrefab, ["wave.ron"]);
+
+const LEVEL_PATH: &str = "levels/main.level.ron";
+const ADVANCE_LEVEL_LABEL: &str = "advance_level";
+const RUN_WAVES_LABEL: &str = "run_waves";
+
+#[derive(Serialize, Deserialize, TypeUuid, Clone)]
+#[uuid = "0b7cbe0b-8d5e-4a51-a7a4-3f0d1c8e6a52"]
+#[serde(rename = "Level")]
+pub struct LevelPrefab {
+    pub name: String,
+    pub waves: Vec<PrefabHandle<WavePrefab>>,
+}
+
+impl Prefab for LevelPrefab {
+    fn apply(&self, entity: Entity, world: &mut World) {
+        let waves = self
+            .waves
+            .iter()
+            .map(|wave| wave.as_handle(world))
+            .collect();
+        world
+            .entity_mut(entity)
+            .insert(Name::new(self.name.clone()))
+            .insert(Level {
+                waves,
+                next_wave: 0,
+                current_wave: None,
+                completed: false,
+            });
+    }
+}
+
+#[derive(Serialize, Deserialize, TypeUuid, Clone)]
+#[uuid = "5d1f0a4e-2c61-4f4b-9d8a-7e0c9b3f12d6"]
+#[serde(rename = "Wave")]
+pub struct WavePrefab {
+    pub groups: Vec<SpawnGroup>,
+}
+
+impl Prefab for WavePrefab {
+    fn apply(&self, entity: Entity, world: &mut World) {
+        let mut groups = self.groups.clone();
+        groups.sort_by(|a, b| a.delay.total_cmp(&b.delay));
+        world.entity_mut(entity).insert(Wave {
+            groups,
+            spawned_groups: 0,
+            timer: Stopwatch::new(),
+        });
+    }
+}
+
+/// A batch of enemies spawned together once `delay` seconds of the wave have passed.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename = "Group")]
+pub struct SpawnGroup {
+    #[serde(default)]
+    pub delay: f32,
+    pub unit: String,
+    #[serde(default = "default_count")]
+    pub count: usize,
+    /// Positions are cycled through if there are fewer than `count`.
+    /// Random positions are used when the list is empty.
+    #[serde(default)]
+    pub positions: Vec<Vec2>,
+    /// Random movement is used when not set.
+    #[serde(default)]
+    pub movement: Option<Movement>,
+}
+
+fn default_count() -> usize {
+    1
+}
+
+#[derive(Component)]
+pub struct Level {
+    pub waves: Vec<Handle<WavePrefab>>,
+    pub next_wave: usize,
+    pub current_wave: Option<Entity>,
+    pub completed: bool,
+}
+
+#[derive(Component)]
+pub struct Wave {
+    pub groups: Vec<SpawnGroup>,
+    pub spawned_groups: usize,
+    pub timer: Stopwatch,
+}
+
+impl Wave {
+    pub fn all_spawned(&self) -> bool {
+        self.spawned_groups >= self.groups.len()
+    }
+}
+
+pub struct WaveClearedEvent {
+    pub wave: usize,
+}
+
+pub struct LevelCompletedEvent;
+
+pub struct WavePlugin;
+
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_prefab::<LevelPrefab>()
+            .register_prefab::<WavePrefab>()
+            .add_asset_loader(LevelLoader)
+            .add_asset_loader(WaveLoader)
+            .add_event::<WaveClearedEvent>()
+            .add_event::<LevelCompletedEvent>()
+            .add_system_set_to_stage(
+                FIXED_UPDATE,
+                SystemSet::on_update(GameState::Gameplay)
+                    .with_system(advance_level.label(ADVANCE_LEVEL_LABEL))
+                    .with_system(
+                        run_waves
+                            .label(RUN_WAVES_LABEL)
+                            .after(ADVANCE_LEVEL_LABEL)
+                            .before(SPAWN_ENEMY_LABEL),
+                    ),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Countdown)
+                    .with_system(despawn_with::<Level>)
+                    .with_system(despawn_with::<Wave>)
+                    .with_system(despawn_with::<Handle<WavePrefab>>)
+                    .with_system(spawn_level),
+            );
+    }
+}
+
+fn spawn_level(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let level_handle: Handle<LevelPrefab> = asset_server.get_handle(LEVEL_PATH);
+    commands.spawn().insert(level_handle);
+}
+
+fn advance_level(
+    mut commands: Commands,
+    mut levels: Query<&mut Level>,
+    waves: Query<Entity, Or<(With<Wave>, With<Handle<WavePrefab>>)>>,
+    mut wave_cleared_events: EventWriter<WaveClearedEvent>,
+    mut level_completed_events: EventWriter<LevelCompletedEvent>,
+) {
+    for mut level in levels.iter_mut() {
+        if level.completed {
+            continue;
+        }
+        if let Some(current_wave) = level.current_wave {
+            if waves.get(current_wave).is_ok() {
+                continue;
+            }
+            wave_cleared_events.send(WaveClearedEvent {
+                wave: level.next_wave - 1,
+            });
+            level.current_wave = None;
+        }
+
+        match level.waves.get(level.next_wave).cloned() {
+            Some(wave_handle) => {
+                let wave_entity = commands
+                    .spawn()
+                    .insert(wave_handle)
+                    .insert(Name::new(format!("Wave {}", level.next_wave + 1)))
+                    .id();
+                level.current_wave = Some(wave_entity);
+                level.next_wave += 1;
+            }
+            None => {
+                level.completed = true;
+                level_completed_events.send(LevelCompletedEvent);
+            }
+        }
+    }
+}
+
+fn run_waves(
+    mut commands: Commands,
+    timestep: Res<Timestep>,
+    asset_server: Res<AssetServer>,
+    mut waves: Query<(Entity, &mut Wave)>,
+    loading_waves: Query<(Entity, &Handle<WavePrefab>, &Name), Without<Wave>>,
+    enemies: Query<(), With<Enemy>>,
+    mut spawn_events: EventWriter<SpawnEnemyEvent>,
+) {
+    // A wave that can't be loaded is skipped instead of holding the level forever.
+    for (entity, handle, name) in loading_waves.iter() {
+        if asset_server.get_load_state(handle) == LoadState::Failed {
+            error!("Unable to load {}, skipping it", name);
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    for (entity, mut wave) in waves.iter_mut() {
+        // Enemies requested on this tick are not spawned yet, so the wave may
+        // only be cleared if everything had been spawned before this tick.
+        if wave.all_spawned() {
+            if enemies.is_empty() {
+                commands.entity(entity).despawn_recursive();
+            }
+            continue;
+        }
+
+        let elapsed = wave.timer.tick(timestep.delta()).elapsed_secs();
+        while let Some(group) = wave.groups.get(wave.spawned_groups) {
+            if group.delay > elapsed {
+                break;
+            }
+            for i in 0..group.count {
+                let position = (!group.positions.is_empty())
+                    .then(|| group.positions[i % group.positions.len()]);
+                spawn_events.send(SpawnEnemyEvent {
+                    unit: asset_server.get_handle(group.unit.as_str()),
+                    position,
+                    movement: group.movement.clone(),
+                });
+            }
+            wave.spawned_groups += 1;
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::{
+    asset::AssetServerSettings,
+    input::{keyboard::KeyboardInput, ElementState},
+    prelude::*,
+};
+
+use game_plugin::{GamePlugin, GameState};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+fn headless_app() -> App {
+    let mut app = App::new();
+    // Tests run from the crate folder, the assets are shared with the game at the workspace root.
+    app.insert_resource(AssetServerSettings {
+        asset_folder: "../assets".into(),
+        ..default()
+    })
+    .add_plugin(GamePlugin::headless().with_seed(0));
+    app
+}
+
+fn current_state(app: &App) -> GameState {
+    app.world.resource::<State<GameState>>().current().clone()
+}
+
+/// Updates the app until it reaches `state`, assets are loaded and timers run in real time.
+fn update_until(app: &mut App, state: GameState) {
+    let start = Instant::now();
+    while current_state(app) != state {
+        assert!(
+            start.elapsed() < TIMEOUT,
+            "Stuck in {:?} while waiting for {:?}",
+            current_state(app),
+            state
+        );
+        app.update();
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+/// Presses and releases a key, going through the same input handling as the keyboard.
+fn tap_key(app: &mut App, key: KeyCode) {
+    for state in [ElementState::Pressed, ElementState::Released] {
+        app.world
+            .resource_mut::<Events<KeyboardInput>>()
+            .send(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key),
+                state,
+            });
+        app.update();
+    }
+}
+
+#[test]
+fn reaches_gameplay() {
+    let mut app = headless_app();
+
+    update_until(&mut app, GameState::MainMenu);
+    tap_key(&mut app, KeyCode::Return);
+    assert_eq!(current_state(&app), GameState::ShipSelect);
+    // Starts with the default ship.
+    tap_key(&mut app, KeyCode::Return);
+    update_until(&mut app, GameState::Countdown);
+    update_until(&mut app, GameState::Gameplay);
+
+    // The simulation keeps running without a window.
+    for _ in 0..10 {
+        app.update();
+    }
+    assert_eq!(current_state(&app), GameState::Gameplay);
+}
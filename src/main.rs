@@ -1,8 +1,36 @@
 use bevy::prelude::*;
 
-use game_plugin::GamePlugin;
+use game_plugin::{GamePlugin, ReplayMode};
 
 fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    let headless = args.iter().any(|arg| arg == "--headless");
+    let seed =
+        arg_value(&args, "--seed").map(|seed| seed.parse::<u64>().expect("Seed must be a number"));
+    let tick_rate = arg_value(&args, "--tick-rate").map(|tick_rate| {
+        tick_rate
+            .parse::<f64>()
+            .expect("Tick rate must be a number")
+    });
+    let replay = arg_value(&args, "--record")
+        .map(|path| ReplayMode::Record(path.into()))
+        .or_else(|| arg_value(&args, "--replay").map(|path| ReplayMode::Playback(path.into())));
+
+    let mut game_plugin = if headless {
+        GamePlugin::headless()
+    } else {
+        GamePlugin::default()
+    };
+    if let Some(seed) = seed {
+        game_plugin = game_plugin.with_seed(seed);
+    }
+    if let Some(tick_rate) = tick_rate {
+        game_plugin = game_plugin.with_tick_rate(tick_rate);
+    }
+    if let Some(replay) = replay {
+        game_plugin = game_plugin.with_replay(replay);
+    }
+
     let mut app = App::new();
     app.insert_resource(WindowDescriptor {
         width: 1280.0,
@@ -11,6 +39,12 @@ fn main() {
         ..default()
     })
     .insert_resource(ClearColor(Color::BLACK))
-    .add_plugin(GamePlugin)
+    .add_plugin(game_plugin)
     .run();
 }
+
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a String> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+}